@@ -222,4 +222,38 @@ mod tests {
             Scalar::from_little_endian_byte_array(&arkworks_bytes.try_into().unwrap()).unwrap();
         assert_eq!(scalar, Scalar::from(x));
     }
+
+    #[test]
+    fn test_from_le_bytes_edge_cases() {
+        let one = Scalar::from(1u128);
+        for (arkworks_scalar, scalar) in [
+            (Fr::zero(), Scalar::zero()),
+            (Fr::from(1u128), one),
+            (-Fr::from(1u128), Scalar::zero() - one),
+        ] {
+            let mut arkworks_bytes = Vec::new();
+            arkworks_scalar
+                .serialize_compressed(&mut arkworks_bytes)
+                .unwrap();
+            let arkworks_bytes: [u8; SCALAR_LENGTH] = arkworks_bytes.try_into().unwrap();
+
+            assert_eq!(
+                Scalar::from_little_endian_byte_array(&arkworks_bytes).unwrap(),
+                scalar
+            );
+            let mut bytes = scalar.to_byte_array();
+            bytes.reverse();
+            assert_eq!(bytes, arkworks_bytes);
+        }
+
+        // The modulus r = (r - 1) + 1 is not a canonical encoding. The least significant byte of
+        // r - 1 is zero, so adding one does not carry.
+        let mut modulus_bytes = Vec::new();
+        (-Fr::from(1u128))
+            .serialize_compressed(&mut modulus_bytes)
+            .unwrap();
+        assert_eq!(modulus_bytes[0], 0);
+        modulus_bytes[0] = 1;
+        assert!(Scalar::from_little_endian_byte_array(&modulus_bytes.try_into().unwrap()).is_err());
+    }
 }
@@ -30,7 +30,7 @@ pub fn verify_groth16_in_bytes(
     proof_points_as_bytes: &[u8],
 ) -> Result<bool, FastCryptoError> {
     if proof_public_inputs_as_bytes.len() % SCALAR_SIZE != 0 {
-        return Err(FastCryptoError::InputLengthWrong(SCALAR_SIZE));
+        return Err(FastCryptoError::InvalidInput);
     }
 
     let pvk = PreparedVerifyingKey::deserialize(&vec![
@@ -53,7 +53,10 @@ impl FieldElement {
     /// Deserialize 32 bytes into a BN254 field element using little-endian format.
     pub(crate) fn deserialize(bytes: &[u8]) -> FastCryptoResult<FieldElement> {
         if bytes.len() != SCALAR_SIZE {
-            return Err(FastCryptoError::InputLengthWrong(bytes.len()));
+            return Err(FastCryptoError::InputLengthWrong {
+                expected: SCALAR_SIZE,
+                actual: bytes.len(),
+            });
         }
         Fr::deserialize_compressed(bytes)
             .map_err(|_| FastCryptoError::InvalidInput)
@@ -66,7 +69,7 @@ impl FieldElement {
         field_element_bytes: &[u8],
     ) -> FastCryptoResult<Vec<FieldElement>> {
         if field_element_bytes.len() % SCALAR_SIZE != 0 {
-            return Err(FastCryptoError::InputLengthWrong(field_element_bytes.len()));
+            return Err(FastCryptoError::InvalidInput);
         }
         let mut public_inputs = Vec::new();
         for chunk in field_element_bytes.chunks(SCALAR_SIZE) {
@@ -48,7 +48,10 @@ macro_rules! define_poseidon_hash {
 /// elements, otherwise an error is returned.
 pub fn poseidon(inputs: &[FieldElement]) -> Result<FieldElement, FastCryptoError> {
     if inputs.is_empty() || inputs.len() > 16 {
-        return Err(FastCryptoError::InputLengthWrong(inputs.len()));
+        return Err(FastCryptoError::InputLengthWrong {
+            expected: inputs.len().clamp(1, 16),
+            actual: inputs.len(),
+        });
     }
     // Instances of Poseidon and PoseidonConstants from neptune have different types depending on
     // the number of inputs, so unfortunately we need to use a macro here.
@@ -1,34 +1,70 @@
 // Copyright (c) 2022, Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
+#[cfg(feature = "client")]
+use std::io::{Read, Write};
 use std::str::FromStr;
+#[cfg(feature = "client")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "client")]
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "client")]
+use std::time::Duration;
 
 use crate::bn254::utils::{
-    gen_address_seed, gen_address_seed_with_salt_hash, get_nonce, get_zk_login_address,
+    assemble_zk_login_signature, candidate_addresses, check_aud_matches, check_authorized_party,
+    check_hosted_domain, derive_new_session, derive_salt_deterministic, extract_address_claims,
+    gen_address_seed, gen_address_seed_with_params, gen_address_seed_with_salt_hash,
+    gen_jwt_randomness, gen_nonces_for_epoch_range, get_nonce, get_nonce_full, get_nonce_strict,
+    get_nonce_with_policy, get_zk_login_address, hash_to_nonce_bytes, jwk_cache_key,
+    jwk_content_cache_key, key_claim_value, parse_jwt_payload, parse_zk_login_signature,
+    provider_from_jwt, split_to_two_frs, validate_eph_pk_consistency, validate_proof_request,
+    verify_ephemeral_signature, verify_jwt_nonce, verify_max_epoch_consistency, verify_nonce,
+    verify_proof_bundle, zk_login_address_eq, zk_login_address_preimage, AddressClaims,
+    CircuitParams, EphemeralKeyScheme, ExtendedEphemeralPublicKey, KeyClaimName, NoncePolicy,
+    CURRENT_CIRCUIT_VERSION, DEFAULT_NONCE_POINTER, NONCE_BYTES_LENGTH,
+};
+#[cfg(feature = "client")]
+use crate::bn254::utils::{
+    get_oidc_url, get_oidc_url_with_key, get_salt, get_salt_with_options, get_salt_with_retry,
+    parse_proof_response, proof_request_body, validate_redirect_uri, OidcUrlBuilder, ProofMetadata,
+    ProofRequestOptions, ProverDialect, SaltRequestOptions, SaltRetryOptions, ZkLoginConfig,
 };
 use crate::bn254::zk_login::big_int_array_to_bits;
 use crate::bn254::zk_login::bitarray_to_bytearray;
+#[cfg(feature = "client")]
+use crate::bn254::zk_login::fetch_jwks;
 use crate::bn254::zk_login::poseidon_zk_login;
+use crate::bn254::zk_login::OIDCProvider;
 use crate::bn254::zk_login::{
     base64_to_bitarray, convert_base, decode_base64_url, hash_ascii_str_to_field, hash_to_field,
     parse_jwks, trim, verify_extended_claim, Claim, JWTDetails, JwkId,
 };
-use crate::bn254::zk_login::{fetch_jwks, OIDCProvider};
+#[cfg(feature = "client")]
+use crate::bn254::zk_login_api::verify_zk_login_with_jwk_refresh;
 use crate::bn254::zk_login_api::ZkLoginEnv;
-use crate::bn254::zk_login_api::{verify_zk_login_id, verify_zk_login_iss, Bn254Fr};
+use crate::bn254::zk_login_api::{
+    batch_verify_zk_login, verify_proof_jwk_current, verify_public_inputs, verify_zk_login_id,
+    verify_zk_login_iss, verify_zk_login_proof_with_fixed_vk, Bn254Fr, PublicInputParams,
+};
 use crate::bn254::{
-    zk_login::{ZkLoginInputs, JWK},
+    zk_login::{ZkLoginInputs, ZkLoginInputsReader, ZkLoginProof, JWK},
     zk_login_api::verify_zk_login,
 };
 use crate::zk_login_utils::Bn254FrElement;
 use ark_bn254::Fr;
+use ark_serialize::CanonicalSerialize;
 use ark_std::rand::rngs::StdRng;
 use ark_std::rand::SeedableRng;
 use fastcrypto::ed25519::Ed25519KeyPair;
 use fastcrypto::encoding::{Encoding, Hex};
 use fastcrypto::error::FastCryptoError;
+use fastcrypto::hash::{Blake2b256, HashFunction};
 use fastcrypto::jwt_utils::JWTHeader;
-use fastcrypto::traits::KeyPair;
+use fastcrypto::rsa::{Base64UrlUnpadded, Encoding as _};
+use fastcrypto::secp256k1::Secp256k1KeyPair;
+use fastcrypto::secp256r1::Secp256r1KeyPair;
+use fastcrypto::traits::{KeyPair, Signer};
 use im::hashmap::HashMap as ImHashMap;
 use num_bigint::BigUint;
 
@@ -90,6 +126,64 @@ const BAD_JWK_BYTES: &[u8] = r#"{
         "keys":[{"alg":"ES256","e":"AQAB","kid":"1","kty":"RSA","n":"6lq9MQ-q6hcxr7kOUp-tHlHtdcDsVLwVIw13iXUCvuDOeCi0VSuxCCUY6UmMjy53dX00ih2E4Y4UvlrmmurK0eG26b-HMNNAvCGsVXHU3RcRhVoHDaOwHwU72j7bpHn9XbP3Q3jebX6KIfNbei2MiR0Wyb8RZHE-aZhRYO8_-k9G2GycTpvc-2GBsP8VHLUKKfAs2B6sW3q3ymU6M0L-cFXkZ9fHkn9ejs-sqZPhMJxtBPBxoUIUQFTgv4VXTSv914f_YkNw-EjuwbgwXMvpyr06EyfImxHoxsZkFYB-qBYHtaMxTnFsZBr6fn8Ha2JqT1hoP7Z5r5wxDu3GQhKkHw","use":"wrong usage"}]
       }"#.as_bytes();
 
+/// The proof from [test_verify_zk_login_google], for the Google JWT with sub 106294049240999307923
+/// signed under max_epoch 10 with the ephemeral key [ed25519_eph_pk_bytes].
+const GOOGLE_PROOF: &str = "{\"proofPoints\":{\"a\":[\"8247215875293406890829839156897863742504615191361518281091302475904551111016\",\"6872980335748205979379321982220498484242209225765686471076081944034292159666\",\"1\"],\"b\":[[\"21419680064642047510915171723230639588631899775315750803416713283740137406807\",\"21566716915562037737681888858382287035712341650647439119820808127161946325890\"],[\"17867714710686394159919998503724240212517838710399045289784307078087926404555\",\"21812769875502013113255155836896615164559280911997219958031852239645061854221\"],[\"1\",\"0\"]],\"c\":[\"7530826803702928198368421787278524256623871560746240215547076095911132653214\",\"16244547936249959771862454850485726883972969173921727256151991751860694123976\",\"1\"]},\"issBase64Details\":{\"value\":\"yJpc3MiOiJodHRwczovL2FjY291bnRzLmdvb2dsZS5jb20iLC\",\"indexMod4\":1},\"headerBase64\":\"eyJhbGciOiJSUzI1NiIsImtpZCI6IjZmNzI1NDEwMWY1NmU0MWNmMzVjOTkyNmRlODRhMmQ1NTJiNGM2ZjEiLCJ0eXAiOiJKV1QifQ\"}";
+
+/// A proof from the prover for a Google JWT with the iss claim `accounts.google.com`, see
+/// [test_alternative_iss_for_google]. It verifies in the test environment for max_epoch 10000
+/// with the ephemeral key [google_alt_iss_eph_pubkey], the address seed
+/// [GOOGLE_ALT_ISS_ADDRESS_SEED] and the JWK [google_alt_iss_jwk].
+const GOOGLE_ALT_ISS_PROOF: &str = "{\"proofPoints\":{\"a\":[\"7566241567720780416751598994698310678767195459947224622023785587667176814058\",\"18104499930818305143361187733659014043953751050617136254447624192327280445771\",\"1\"],\"b\":[[\"11369230593957954942221175389182778816136534144714579815927653075736806430994\",\"11928003240637992017698644299021052465098754853899210401706726930513411198353\"],[\"2597127058046351054449743605218058440565462021354202666955356076272028963802\",\"3385145993275542896693643488618289924488296318344621918448585222369718288892\"],[\"1\",\"0\"]],\"c\":[\"395141536511114303768253959602639884294254888080713473665269769443249414257\",\"21430657725804540809568084344756144327539843580919730138594118365564728808275\",\"1\"]},\"issBase64Details\":{\"value\":\"yJpc3MiOiJodHRwczovL2FjY291bnRzLmdvb2dsZS5jb20iLC\",\"indexMod4\":1},\"headerBase64\":\"eyJhbGciOiJSUzI1NiIsImtpZCI6ImM5YWZkYTM2ODJlYmYwOWViMzA1NWMxYzRiZDM5Yjc1MWZiZjgxOTUiLCJ0eXAiOiJKV1QifQ\"}";
+
+/// [GOOGLE_ALT_ISS_PROOF] with invalid proof points.
+const GOOGLE_ALT_ISS_INVALID_PROOF: &str = "{\"proofPoints\":{\"a\":[\"1\",\"18104499930818305143361187733659014043953751050617136254447624192327280445771\",\"1\"],\"b\":[[\"1\",\"11928003240637992017698644299021052465098754853899210401706726930513411198353\"],[\"2597127058046351054449743605218058440565462021354202666955356076272028963802\",\"3385145993275542896693643488618289924488296318344621918448585222369718288892\"],[\"1\",\"0\"]],\"c\":[\"395141536511114303768253959602639884294254888080713473665269769443249414257\",\"21430657725804540809568084344756144327539843580919730138594118365564728808275\",\"1\"]},\"issBase64Details\":{\"value\":\"yJpc3MiOiJodHRwczovL2FjY291bnRzLmdvb2dsZS5jb20iLC\",\"indexMod4\":1},\"headerBase64\":\"eyJhbGciOiJSUzI1NiIsImtpZCI6ImM5YWZkYTM2ODJlYmYwOWViMzA1NWMxYzRiZDM5Yjc1MWZiZjgxOTUiLCJ0eXAiOiJKV1QifQ\"}";
+
+const GOOGLE_ALT_ISS_ADDRESS_SEED: &str =
+    "4959624758616676340947699768172740454110375485415332267384397278368360470616";
+
+/// The modulus of the JWK [GOOGLE_ALT_ISS_PROOF] was generated against.
+const GOOGLE_ALT_ISS_JWK_N: &str = "whYOFK2Ocbbpb_zVypi9SeKiNUqKQH0zTKN1-6fpCTu6ZalGI82s7XK3tan4dJt90ptUPKD2zvxqTzFNfx4HHHsrYCf2-FMLn1VTJfQazA2BvJqAwcpW1bqRUEty8tS_Yv4hRvWfQPcc2Gc3-_fQOOW57zVy-rNoJc744kb30NjQxdGp03J2S3GLQu7oKtSDDPooQHD38PEMNnITf0pj-KgDPjymkMGoJlO3aKppsjfbt_AH6GGdRghYRLOUwQU-h-ofWHR3lbYiKtXPn5dN24kiHy61e3VAQ9_YAZlwXC_99GGtw_NpghFAuM4P1JDn0DppJldy3PGFC0GfBCZASw";
+
+/// The ephemeral public key, as flag || pk_bytes, [GOOGLE_ALT_ISS_PROOF] was generated for.
+fn google_alt_iss_eph_pubkey() -> Vec<u8> {
+    let mut eph_pubkey_bytes = vec![0];
+    eph_pubkey_bytes.extend(
+        BigUint::from_str(
+            "3598866369818193253063936208363210863933653800990958031560302098730308306242903464",
+        )
+        .unwrap()
+        .to_bytes_be(),
+    );
+    eph_pubkey_bytes
+}
+
+/// The Google JWK [GOOGLE_ALT_ISS_PROOF] was generated against.
+fn google_alt_iss_jwk() -> (JwkId, JWK) {
+    (
+        JwkId::new(
+            OIDCProvider::Google.get_config().iss,
+            "c9afda3682ebf09eb3055c1c4bd39b751fbf8195".to_string(),
+        ),
+        JWK {
+            kty: "RSA".to_string(),
+            e: "AQAB".to_string(),
+            n: GOOGLE_ALT_ISS_JWK_N.to_string(),
+            alg: "RS256".to_string(),
+        },
+    )
+}
+
+/// The ephemeral public key, as flag || pk_bytes, of the Ed25519 key pair generated from the seed
+/// [0; 32]. With max_epoch 10 and the randomness 100681567828351849884072155819400689117 its nonce
+/// is hTPpgF7XAKbW37rEUS6pEVZqmoI, see [test_verify_nonce].
+fn ed25519_eph_pk_bytes() -> Vec<u8> {
+    let kp = Ed25519KeyPair::generate(&mut StdRng::from_seed([0; 32]));
+    let mut eph_pk_bytes = vec![0x00];
+    eph_pk_bytes.extend(kp.public().as_ref());
+    eph_pk_bytes
+}
+
 #[tokio::test]
 async fn test_verify_zk_login_google() {
     let user_salt = "206703048842351542647799591018316385612";
@@ -150,6 +244,201 @@ async fn test_verify_zk_login_google() {
     assert!(res.is_ok());
 }
 
+#[test]
+fn test_assemble_zk_login_signature() {
+    // Test vector from [test_verify_zk_login_google]
+    let kp = Ed25519KeyPair::generate(&mut StdRng::from_seed([0; 32]));
+    let mut eph_pubkey = vec![0x00];
+    eph_pubkey.extend(kp.public().as_ref());
+    let address_seed = gen_address_seed(
+        "206703048842351542647799591018316385612",
+        "sub",
+        "106294049240999307923",
+        "25769832374-famecqrhe2gkebt5fvqms2263046lj96.apps.googleusercontent.com",
+    )
+    .unwrap();
+    let zk_login_inputs = ZkLoginInputs::from_json(GOOGLE_PROOF, &address_seed).unwrap();
+    let signature = kp.sign(b"transaction data");
+
+    let bytes =
+        assemble_zk_login_signature(&zk_login_inputs, 10, signature.as_ref(), &eph_pubkey).unwrap();
+    assert_eq!(bytes[0], 0x05);
+
+    let (inputs, max_epoch, user_signature): (ZkLoginInputs, u64, Vec<u8>) =
+        bcs::from_bytes(&bytes[1..]).unwrap();
+    assert_eq!(
+        bcs::to_bytes(&inputs).unwrap(),
+        bcs::to_bytes(&zk_login_inputs).unwrap()
+    );
+    assert_eq!(max_epoch, 10);
+    assert_eq!(user_signature[0], 0x00);
+    assert_eq!(&user_signature[1..65], signature.as_ref());
+    assert_eq!(&user_signature[65..], kp.public().as_ref());
+
+    let parsed = parse_zk_login_signature(&bytes).unwrap();
+    assert_eq!(
+        bcs::to_bytes(&parsed.inputs).unwrap(),
+        bcs::to_bytes(&zk_login_inputs).unwrap()
+    );
+    assert_eq!(parsed.inputs.get_kid(), zk_login_inputs.get_kid());
+    assert_eq!(parsed.max_epoch, 10);
+    assert_eq!(parsed.eph_signature, signature.as_ref());
+    assert_eq!(parsed.eph_pk_bytes, eph_pubkey);
+
+    // A wrong flag, truncated bytes or an invalid user signature are rejected.
+    let mut wrong_flag = bytes.clone();
+    wrong_flag[0] = 0x00;
+    assert!(parse_zk_login_signature(&wrong_flag).is_err());
+    assert!(parse_zk_login_signature(&bytes[..bytes.len() - 1]).is_err());
+    assert_eq!(
+        parse_zk_login_signature(&[]).unwrap_err(),
+        FastCryptoError::InputLengthWrong {
+            expected: 1,
+            actual: 0,
+        }
+    );
+    let mut short_user_signature = vec![0x05];
+    short_user_signature.extend(bcs::to_bytes(&(&zk_login_inputs, 10u64, vec![0u8; 65])).unwrap());
+    assert!(parse_zk_login_signature(&short_user_signature).is_err());
+
+    assert_eq!(
+        assemble_zk_login_signature(&zk_login_inputs, 10, &[0; 63], &eph_pubkey),
+        Err(FastCryptoError::InputLengthWrong {
+            expected: 64,
+            actual: 63,
+        })
+    );
+    assert_eq!(
+        assemble_zk_login_signature(&zk_login_inputs, 10, signature.as_ref(), &[0x03; 33]),
+        Err(FastCryptoError::InvalidInput)
+    );
+}
+
+#[test]
+fn test_verify_ephemeral_signature() {
+    let message = b"transaction data";
+    let mut rng = StdRng::from_seed([0; 32]);
+
+    let ed25519 = Ed25519KeyPair::generate(&mut rng);
+    let mut ed25519_pk = vec![0x00];
+    ed25519_pk.extend(ed25519.public().as_ref());
+    let ed25519_sig = ed25519.sign(message);
+
+    let secp256k1 = Secp256k1KeyPair::generate(&mut rng);
+    let mut secp256k1_pk = vec![0x01];
+    secp256k1_pk.extend(secp256k1.public().as_ref());
+    let secp256k1_sig = secp256k1.sign(message);
+
+    let secp256r1 = Secp256r1KeyPair::generate(&mut rng);
+    let mut secp256r1_pk = vec![0x02];
+    secp256r1_pk.extend(secp256r1.public().as_ref());
+    let secp256r1_sig = secp256r1.sign(message);
+
+    for (pk, sig) in [
+        (&ed25519_pk, ed25519_sig.as_ref()),
+        (&secp256k1_pk, secp256k1_sig.as_ref()),
+        (&secp256r1_pk, secp256r1_sig.as_ref()),
+    ] {
+        assert!(verify_ephemeral_signature(pk, message, sig).is_ok());
+        assert!(verify_ephemeral_signature(pk, b"other data", sig).is_err());
+        assert!(verify_ephemeral_signature(pk, message, &sig[..63]).is_err());
+    }
+
+    // The signature is verified with the scheme of the flag.
+    assert!(verify_ephemeral_signature(&secp256k1_pk, message, secp256r1_sig.as_ref()).is_err());
+    let mut wrong_scheme = secp256k1_pk.clone();
+    wrong_scheme[0] = 0x02;
+    assert!(verify_ephemeral_signature(&wrong_scheme, message, secp256k1_sig.as_ref()).is_err());
+
+    // Unknown flags and wrong key lengths are rejected.
+    let mut unknown_flag = ed25519_pk.clone();
+    unknown_flag[0] = 0x03;
+    assert_eq!(
+        verify_ephemeral_signature(&unknown_flag, message, ed25519_sig.as_ref()),
+        Err(FastCryptoError::InvalidInput)
+    );
+    assert_eq!(
+        verify_ephemeral_signature(&ed25519_pk[..32], message, ed25519_sig.as_ref()),
+        Err(FastCryptoError::InputLengthWrong {
+            expected: 32,
+            actual: 31,
+        })
+    );
+    assert_eq!(
+        verify_ephemeral_signature(&[], message, ed25519_sig.as_ref()),
+        Err(FastCryptoError::InputLengthWrong {
+            expected: 1,
+            actual: 0,
+        })
+    );
+}
+
+#[test]
+fn test_verify_public_inputs() {
+    // Test vector from [test_verify_zk_login_google]
+    let reader: ZkLoginInputsReader = serde_json::from_str(GOOGLE_PROOF).unwrap();
+    let eph_pk_bytes = ed25519_eph_pk_bytes();
+    let params = PublicInputParams {
+        iss: OIDCProvider::Google.get_config().iss,
+        aud: "25769832374-famecqrhe2gkebt5fvqms2263046lj96.apps.googleusercontent.com".to_string(),
+        sub: "106294049240999307923".to_string(),
+        salt: "206703048842351542647799591018316385612".to_string(),
+        eph_pk_bytes,
+        max_epoch: 10,
+        jwk: JWK {
+            kty: "RSA".to_string(),
+            e: "AQAB".to_string(),
+            n: "oUriU8GqbRw-avcMn95DGW1cpZR1IoM6L7krfrWvLSSCcSX6Ig117o25Yk7QWBiJpaPV0FbP7Y5-DmThZ3SaF0AXW-3BsKPEXfFfeKVc6vBqk3t5mKlNEowjdvNTSzoOXO5UIHwsXaxiJlbMRalaFEUm-2CKgmXl1ss_yGh1OHkfnBiGsfQUndKoHiZuDzBMGw8Sf67am_Ok-4FShK0NuR3-q33aB_3Z7obC71dejSLWFOEcKUVCaw6DGVuLog3x506h1QQ1r0FXKOQxnmqrRgpoHqGSouuG35oZve1vgCU4vLZ6EAgBAbC0KL35I7_0wUDSMpiAvf7iZxzJVbspkQ".to_string(),
+            alg: "RS256".to_string(),
+        },
+        env: ZkLoginEnv::Prod,
+    };
+    assert!(verify_public_inputs(&reader, &params).is_ok());
+
+    // Any parameter differing from what the proof was generated for is detected.
+    let mismatch = Err(FastCryptoError::GeneralError(
+        "Proof does not match the public inputs".to_string(),
+    ));
+    let other_sub = PublicInputParams {
+        sub: "106294049240999307924".to_string(),
+        ..params.clone()
+    };
+    assert_eq!(verify_public_inputs(&reader, &other_sub), mismatch);
+    let other_salt = PublicInputParams {
+        salt: "206703048842351542647799591018316385613".to_string(),
+        ..params.clone()
+    };
+    assert_eq!(verify_public_inputs(&reader, &other_salt), mismatch);
+    let other_epoch = PublicInputParams {
+        max_epoch: 11,
+        ..params.clone()
+    };
+    assert_eq!(verify_public_inputs(&reader, &other_epoch), mismatch);
+    let other_iss = PublicInputParams {
+        iss: "https://accounts.other.com".to_string(),
+        ..params
+    };
+    assert!(verify_public_inputs(&reader, &other_iss).is_err());
+}
+
+#[test]
+fn test_reject_insecure_alg() {
+    let header = |alg: &str| -> JWTHeader {
+        serde_json::from_value(serde_json::json!({ "alg": alg, "kid": "1" })).unwrap()
+    };
+    assert!(header("RS256").reject_insecure_alg().is_ok());
+    for alg in ["none", "None", "HS256", "ES256", "RS512", ""] {
+        assert_eq!(
+            header(alg).reject_insecure_alg(),
+            Err(FastCryptoError::InvalidInput)
+        );
+        let header_base64 = Base64UrlUnpadded::encode_string(
+            serde_json::to_string(&header(alg)).unwrap().as_bytes(),
+        );
+        assert!(JWTHeader::new(&header_base64).is_err());
+    }
+}
+
 #[test]
 fn test_parse_jwt_details() {
     let header = JWTHeader::new("eyJhbGciOiJSUzI1NiIsInR5cCI6IkpXVCIsImtpZCI6IjEifQ").unwrap();
@@ -362,6 +651,20 @@ fn test_hash_ascii_str_to_field() {
             .to_string(),
         "10859137172532636243875876865378218840892896099608302223608404291948352005840"
     );
+
+    // Multibyte characters are rejected rather than hashed by code point.
+    assert_eq!(
+        hash_ascii_str_to_field("tést@gmail.com", 30),
+        Err(FastCryptoError::InvalidInput)
+    );
+    assert_eq!(
+        hash_ascii_str_to_field("用户@gmail.com", 30),
+        Err(FastCryptoError::InvalidInput)
+    );
+    assert_eq!(
+        gen_address_seed("1", "email", "tést@gmail.com", "aud"),
+        Err(FastCryptoError::InvalidInput)
+    );
 }
 
 #[test]
@@ -428,6 +731,7 @@ fn test_jwk_parse() {
     .is_err());
 }
 
+#[cfg(feature = "client")]
 #[tokio::test]
 async fn test_get_jwks() {
     let client = reqwest::Client::new();
@@ -456,167 +760,1403 @@ async fn test_get_jwks() {
     }
 }
 
+#[cfg(feature = "client")]
 #[test]
-fn test_get_nonce() {
+fn test_get_oidc_url_with_key() {
     let kp = Ed25519KeyPair::generate(&mut StdRng::from_seed([0; 32]));
+    let eph_pk = ExtendedEphemeralPublicKey::new(0x00, kp.public().as_ref()).unwrap();
     let mut eph_pk_bytes = vec![0x00];
     eph_pk_bytes.extend(kp.public().as_ref());
-    let nonce = get_nonce(&eph_pk_bytes, 10, "100681567828351849884072155819400689117").unwrap();
-    assert_eq!(nonce, "hTPpgF7XAKbW37rEUS6pEVZqmoI");
-}
+    assert_eq!(eph_pk.as_bytes(), eph_pk_bytes.as_slice());
+    assert_eq!(
+        ExtendedEphemeralPublicKey::from_bytes(&eph_pk_bytes).unwrap(),
+        eph_pk
+    );
 
-#[test]
-fn test_get_provider_to_from_iss_to_from_str() {
-    for p in [
-        OIDCProvider::Facebook,
+    let jwt_randomness = "100681567828351849884072155819400689117";
+    let url = get_oidc_url_with_key(
         OIDCProvider::Google,
-        OIDCProvider::Twitch,
-        OIDCProvider::Slack,
-        OIDCProvider::Kakao,
-        OIDCProvider::Apple,
-        OIDCProvider::Microsoft,
-        OIDCProvider::AwsTenant(("us-east-1".to_string(), "us-east-1_LPSLCkC3A".to_string())),
-        OIDCProvider::AwsTenant(("us-east-1".to_string(), "us-east-1_qPsZxYqd8".to_string())),
-        OIDCProvider::KarrierOne,
-        OIDCProvider::Credenza3,
-        OIDCProvider::Playtron,
-        OIDCProvider::Threedos,
-        OIDCProvider::Onefc,
-        OIDCProvider::FanTV,
-    ] {
-        // to/from iss
-        assert_eq!(p, OIDCProvider::from_iss(&p.get_config().iss).unwrap());
-        // to/from string
-        assert_eq!(p, OIDCProvider::from_str(&p.to_string()).unwrap());
-    }
-    assert!(OIDCProvider::from_iss("random").is_err());
-    assert!(OIDCProvider::from_str("random").is_err());
-}
-
-#[test]
-fn test_gen_seed() {
-    let address_seed = gen_address_seed(
-        "248191903847969014646285995941615069143",
-        "sub",
-        "904448692",
-        "rs1bh065i9ya4ydvifixl4kss0uhpt",
+        &eph_pk,
+        10,
+        "client_id",
+        "https://sui.io/callback",
+        jwt_randomness,
     )
     .unwrap();
     assert_eq!(
-        address_seed.to_string(),
-        "16657007263003735230240998439420301694514420923267872433517882233836276100450".to_string()
+        url,
+        get_oidc_url(
+            OIDCProvider::Google,
+            &eph_pk_bytes,
+            10,
+            "client_id",
+            "https://sui.io/callback",
+            jwt_randomness,
+        )
+        .unwrap()
     );
+
+    // The public key length must match the scheme.
+    assert_eq!(
+        ExtendedEphemeralPublicKey::new(0x00, &[1; 33]),
+        Err(FastCryptoError::InputLengthWrong {
+            expected: 32,
+            actual: 33,
+        })
+    );
+    assert!(ExtendedEphemeralPublicKey::new(0x01, &[1; 33]).is_ok());
+    assert!(ExtendedEphemeralPublicKey::new(0x02, &[1; 32]).is_err());
+    assert!(ExtendedEphemeralPublicKey::new(0x03, &[1; 32]).is_err());
+    assert!(ExtendedEphemeralPublicKey::from_bytes(&[]).is_err());
 }
 
+#[cfg(feature = "client")]
 #[test]
-fn test_verify_zk_login() {
-    // Test vector from [test_verify_zk_login_google]
-    let address =
-        hex::decode("1c6b623a2f2c91333df730c98d220f11484953b391a3818680f922c264cc0c6b").unwrap();
-    let name = "sub";
-    let value = "106294049240999307923";
-    let aud = "575519204237-msop9ep45u2uo98hapqmngv8d84qdc8k.apps.googleusercontent.com";
-    let salt = "6588741469050502421550140105345050859";
-    let iss = "https://accounts.google.com";
-    let salt_hash = poseidon_zk_login(&[(&Bn254FrElement::from_str(salt).unwrap()).into()])
+fn test_oidc_url_builder() {
+    let eph_pk_bytes = ed25519_eph_pk_bytes();
+    let jwt_randomness = "100681567828351849884072155819400689117";
+
+    let url = OidcUrlBuilder::new(OIDCProvider::Google)
+        .client_id("client_id")
+        .redirect_url("https://sui.io/callback")
+        .eph_pk(&eph_pk_bytes)
+        .max_epoch(10)
+        .jwt_randomness(jwt_randomness)
+        .build()
+        .unwrap();
+    assert_eq!(
+        url,
+        get_oidc_url(
+            OIDCProvider::Google,
+            &eph_pk_bytes,
+            10,
+            "client_id",
+            "https://sui.io/callback",
+            jwt_randomness,
+        )
         .unwrap()
-        .to_string();
-    assert!(verify_zk_login_id(&address, name, value, aud, iss, &salt_hash).is_ok());
+    );
+    assert!(url.ends_with("&nonce=hTPpgF7XAKbW37rEUS6pEVZqmoI"));
 
-    let address_seed = gen_address_seed_with_salt_hash(&salt_hash, name, value, aud).unwrap();
-    assert!(verify_zk_login_iss(&address, &address_seed, iss).is_ok());
+    assert_eq!(
+        OidcUrlBuilder::new(OIDCProvider::Google)
+            .client_id("client_id")
+            .max_epoch(10)
+            .build(),
+        Err(FastCryptoError::GeneralError(
+            "Missing fields for OIDC URL: redirect_url, eph_pk, jwt_randomness".to_string()
+        ))
+    );
+
+    // Errors from get_oidc_url are passed through.
+    assert!(OidcUrlBuilder::new(OIDCProvider::Google)
+        .client_id("client_id")
+        .redirect_url("http://sui.io/callback")
+        .eph_pk(&eph_pk_bytes)
+        .max_epoch(10)
+        .jwt_randomness(jwt_randomness)
+        .build()
+        .is_err());
+}
+
+#[test]
+fn test_get_nonce_strict() {
+    let eph_pk_bytes = ed25519_eph_pk_bytes();
+    let jwt_randomness = "100681567828351849884072155819400689117";
 
-    let other_iss = "https://some.other.issuer.com";
     assert_eq!(
-        verify_zk_login_id(&address, name, value, aud, other_iss, &salt_hash),
-        Err(FastCryptoError::InvalidProof)
+        validate_eph_pk_consistency(&eph_pk_bytes),
+        Ok(EphemeralKeyScheme::Ed25519)
     );
     assert_eq!(
-        verify_zk_login_iss(&address, &address_seed, other_iss),
-        Err(FastCryptoError::InvalidProof)
+        get_nonce_strict(&eph_pk_bytes, 10, jwt_randomness).unwrap(),
+        "hTPpgF7XAKbW37rEUS6pEVZqmoI"
     );
 
-    let too_long_kc_name = "subsubsubsubsubsubsubsubsubsubsubsub";
     assert_eq!(
-        verify_zk_login_id(
-            &address,
-            too_long_kc_name,
-            value,
-            aud,
-            other_iss,
-            &salt_hash
-        ),
-        Err(FastCryptoError::GeneralError("in_arr too long".to_string()))
+        validate_eph_pk_consistency(&[0x01; 34]),
+        Ok(EphemeralKeyScheme::Secp256k1)
+    );
+    assert_eq!(
+        validate_eph_pk_consistency(&[0x02; 34]),
+        Ok(EphemeralKeyScheme::Secp256r1)
     );
+    for scheme in [
+        EphemeralKeyScheme::Ed25519,
+        EphemeralKeyScheme::Secp256k1,
+        EphemeralKeyScheme::Secp256r1,
+    ] {
+        assert_eq!(EphemeralKeyScheme::from_flag(scheme.flag()), Ok(scheme));
+    }
 
-    let too_long_kc_value = "106294049240999307923106294049240999307923106294049240999307923106294049240999307923106294049240999307923106294049240999307923";
+    // An Ed25519 flag followed by a 33 byte key gives a nonce with get_nonce but is rejected here.
+    let mut inconsistent = vec![0x00];
+    inconsistent.extend([1; 33]);
+    assert!(get_nonce(&inconsistent, 10, jwt_randomness).is_ok());
     assert_eq!(
-        verify_zk_login_id(
-            &address,
-            name,
-            too_long_kc_value,
-            aud,
-            other_iss,
-            &salt_hash
-        ),
-        Err(FastCryptoError::GeneralError("in_arr too long".to_string()))
+        get_nonce_strict(&inconsistent, 10, jwt_randomness),
+        Err(FastCryptoError::InputLengthWrong {
+            expected: 32,
+            actual: 33,
+        })
+    );
+    assert_eq!(
+        validate_eph_pk_consistency(&[0x03; 33]),
+        Err(FastCryptoError::InvalidInput)
+    );
+    assert_eq!(
+        validate_eph_pk_consistency(&[]),
+        Err(FastCryptoError::InputLengthWrong {
+            expected: 1,
+            actual: 0,
+        })
     );
 }
 
+#[cfg(feature = "client")]
 #[test]
-fn test_all_inputs_hash() {
-    let jwt_sha2_hash_0 = Fr::from_str("248987002057371616691124650904415756047").unwrap();
-    let jwt_sha2_hash_1 = Fr::from_str("113498781424543581252500776698433499823").unwrap();
-    let masked_content_hash = Fr::from_str(
-        "14900420995580824499222150327925943524564997104405553289134597516335134742309",
-    )
-    .unwrap();
-    let payload_start_index = Fr::from_str("103").unwrap();
-    let payload_len = Fr::from_str("564").unwrap();
-    let eph_public_key_0 = Fr::from_str("17932473587154777519561053972421347139").unwrap();
-    let eph_public_key_1 = Fr::from_str("134696963602902907403122104327765350261").unwrap();
-    let max_epoch = Fr::from_str("10000").unwrap();
-    let num_sha2_blocks = Fr::from_str("11").unwrap();
-    let key_claim_name_f = Fr::from_str(
-        "18523124550523841778801820019979000409432455608728354507022210389496924497355",
-    )
-    .unwrap();
-    let addr_seed = Fr::from_str(
-        "15604334753912523265015800787270404628529489918817818174033741053550755333691",
-    )
-    .unwrap();
+fn test_proof_request_body() {
+    let body = proof_request_body(
+        "jwt",
+        10,
+        "100681567828351849884072155819400689117",
+        "84029355920633174015103288781128426107680789454168570548782290541079926444544",
+        "248191903847969014646285995941615069143",
+        &ProofRequestOptions::default(),
+    );
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "jwt": "jwt",
+            "extendedEphemeralPublicKey": "84029355920633174015103288781128426107680789454168570548782290541079926444544",
+            "maxEpoch": 10,
+            "jwtRandomness": "100681567828351849884072155819400689117",
+            "salt": "248191903847969014646285995941615069143",
+            "keyClaimName": "sub",
+        })
+    );
 
-    let hash = poseidon_zk_login(&[
-        jwt_sha2_hash_0,
-        jwt_sha2_hash_1,
-        masked_content_hash,
-        payload_start_index,
-        payload_len,
-        eph_public_key_0,
-        eph_public_key_1,
-        max_epoch,
-        num_sha2_blocks,
-        key_claim_name_f,
-        addr_seed,
-    ])
-    .unwrap();
+    let versioned = proof_request_body(
+        "jwt",
+        10,
+        "100681567828351849884072155819400689117",
+        "84029355920633174015103288781128426107680789454168570548782290541079926444544",
+        "248191903847969014646285995941615069143",
+        &ProofRequestOptions {
+            circuit_version: Some(2),
+            ..Default::default()
+        },
+    );
+    assert_eq!(versioned["circuitVersion"], 2);
+    assert_eq!(versioned.as_object().unwrap().len(), 7);
+
+    let snake_case = proof_request_body(
+        "jwt",
+        10,
+        "100681567828351849884072155819400689117",
+        "84029355920633174015103288781128426107680789454168570548782290541079926444544",
+        "248191903847969014646285995941615069143",
+        &ProofRequestOptions {
+            circuit_version: Some(2),
+            dialect: ProverDialect::SnakeCase,
+        },
+    );
     assert_eq!(
-        hash.to_string(),
-        "2487117669597822357956926047501254969190518860900347921480370492048882803688".to_string()
+        snake_case,
+        serde_json::json!({
+            "jwt": "jwt",
+            "extended_ephemeral_public_key": "84029355920633174015103288781128426107680789454168570548782290541079926444544",
+            "max_epoch": 10,
+            "jwt_randomness": "100681567828351849884072155819400689117",
+            "salt": "248191903847969014646285995941615069143",
+            "key_claim_name": "sub",
+            "circuit_version": 2,
+        })
     );
 }
+
 #[test]
-fn test_alternative_iss_for_google() {
-    let input = ZkLoginInputs::from_json("{\"proofPoints\":{\"a\":[\"7566241567720780416751598994698310678767195459947224622023785587667176814058\",\"18104499930818305143361187733659014043953751050617136254447624192327280445771\",\"1\"],\"b\":[[\"11369230593957954942221175389182778816136534144714579815927653075736806430994\",\"11928003240637992017698644299021052465098754853899210401706726930513411198353\"],[\"2597127058046351054449743605218058440565462021354202666955356076272028963802\",\"3385145993275542896693643488618289924488296318344621918448585222369718288892\"],[\"1\",\"0\"]],\"c\":[\"395141536511114303768253959602639884294254888080713473665269769443249414257\",\"21430657725804540809568084344756144327539843580919730138594118365564728808275\",\"1\"]},\"issBase64Details\":{\"value\":\"yJpc3MiOiJodHRwczovL2FjY291bnRzLmdvb2dsZS5jb20iLC\",\"indexMod4\":1},\"headerBase64\":\"eyJhbGciOiJSUzI1NiIsImtpZCI6ImM5YWZkYTM2ODJlYmYwOWViMzA1NWMxYzRiZDM5Yjc1MWZiZjgxOTUiLCJ0eXAiOiJKV1QifQ\"}", "4959624758616676340947699768172740454110375485415332267384397278368360470616").unwrap();
-    let invalid_proof_input = ZkLoginInputs::from_json("{\"proofPoints\":{\"a\":[\"1\",\"18104499930818305143361187733659014043953751050617136254447624192327280445771\",\"1\"],\"b\":[[\"1\",\"11928003240637992017698644299021052465098754853899210401706726930513411198353\"],[\"2597127058046351054449743605218058440565462021354202666955356076272028963802\",\"3385145993275542896693643488618289924488296318344621918448585222369718288892\"],[\"1\",\"0\"]],\"c\":[\"395141536511114303768253959602639884294254888080713473665269769443249414257\",\"21430657725804540809568084344756144327539843580919730138594118365564728808275\",\"1\"]},\"issBase64Details\":{\"value\":\"yJpc3MiOiJodHRwczovL2FjY291bnRzLmdvb2dsZS5jb20iLC\",\"indexMod4\":1},\"headerBase64\":\"eyJhbGciOiJSUzI1NiIsImtpZCI6ImM5YWZkYTM2ODJlYmYwOWViMzA1NWMxYzRiZDM5Yjc1MWZiZjgxOTUiLCJ0eXAiOiJKV1QifQ\"}", "4959624758616676340947699768172740454110375485415332267384397278368360470616").unwrap();
-    let _ = ZkLoginInputs::from_json("{\"proofPoints\":{\"a\":[\"18104499930818305143361187733659014043953751050617136254447624192327280445771\",\"1\"],\"b\":[[\"11369230593957954942221175389182778816136534144714579815927653075736806430994\",\"11928003240637992017698644299021052465098754853899210401706726930513411198353\"],[\"2597127058046351054449743605218058440565462021354202666955356076272028963802\",\"3385145993275542896693643488618289924488296318344621918448585222369718288892\"],[\"1\",\"0\"]],\"c\":[\"395141536511114303768253959602639884294254888080713473665269769443249414257\",\"21430657725804540809568084344756144327539843580919730138594118365564728808275\",\"1\"]},\"issBase64Details\":{\"value\":\"yJpc3MiOiJodHRwczovL2FjY291bnRzLmdvb2dsZS5jb20iLC\",\"indexMod4\":1},\"headerBase64\":\"eyJhbGciOiJSUzI1NiIsImtpZCI6ImM5YWZkYTM2ODJlYmYwOWViMzA1NWMxYzRiZDM5Yjc1MWZiZjgxOTUiLCJ0eXAiOiJKV1QifQ\"}", "4959624758616676340947699768172740454110375485415332267384397278368360470616").is_err();
-    let _ = ZkLoginInputs::from_json("{\"proofPoints\":{\"a\":[\"18104499930818305143361187733659014043953751050617136254447624192327280445771\",\"1\"],\"b\":[[\"11369230593957954942221175389182778816136534144714579815927653075736806430994\",\"11928003240637992017698644299021052465098754853899210401706726930513411198353\"],[\"2597127058046351054449743605218058440565462021354202666955356076272028963802\",\"3385145993275542896693643488618289924488296318344621918448585222369718288892\"],[\"1\",\"0\"]],\"c\":[\"395141536511114303768253959602639884294254888080713473665269769443249414257\",\"21430657725804540809568084344756144327539843580919730138594118365564728808275\",\"1\"]},\"issBase64Details\":{\"value\":\"yJpc3MiOiJodHRwczovL2FjY291bnRzLmdvb2dsZS5jb20iLC\",\"indexMod4\":1},\"headerBase64\":\"eyJhbGciOiJSUzI1NiIsImtpZCI6ImM5YWZkYTM2ODJlYmYwOWViMzA1NWMxYzRiZDM5Yjc1MWZiZjgxOTUiLCJ0eXAiOiJKV1QifQ\"}", "bad seed").is_err();
+fn test_validate_proof_request() {
+    let jwt = jwt_with_payload(serde_json::json!({
+        "iss": "https://accounts.google.com",
+        "sub": "106294049240999307923",
+        "nonce": "hTPpgF7XAKbW37rEUS6pEVZqmoI",
+    }));
+    let jwt_randomness = "100681567828351849884072155819400689117";
+    let eph_pubkey =
+        "84029355920633174015103288781128426107680789454168570548782290541079926444544";
+    let salt = "248191903847969014646285995941615069143";
+    let invalid = |name: &str| Err(FastCryptoError::GeneralError(format!("Invalid {}", name)));
 
-    let mut eph_pubkey_bytes = vec![0];
-    eph_pubkey_bytes.extend(
-        BigUint::from_str(
-            "3598866369818193253063936208363210863933653800990958031560302098730308306242903464",
-        )
+    assert!(validate_proof_request(&jwt, 10, jwt_randomness, eph_pubkey, salt).is_ok());
+
+    assert_eq!(
+        validate_proof_request("jwt", 10, jwt_randomness, eph_pubkey, salt),
+        invalid("jwt")
+    );
+    let jwt_without_nonce = jwt_with_payload(serde_json::json!({
+        "iss": "https://accounts.google.com",
+        "sub": "106294049240999307923",
+    }));
+    assert_eq!(
+        validate_proof_request(&jwt_without_nonce, 10, jwt_randomness, eph_pubkey, salt),
+        invalid("jwt claim nonce")
+    );
+    let numeric_sub = jwt_with_payload(serde_json::json!({
+        "iss": "https://accounts.google.com",
+        "sub": 123,
+        "nonce": "hTPpgF7XAKbW37rEUS6pEVZqmoI",
+    }));
+    assert_eq!(
+        validate_proof_request(&numeric_sub, 10, jwt_randomness, eph_pubkey, salt),
+        invalid("jwt claim sub")
+    );
+    assert_eq!(
+        validate_proof_request(&jwt, 0, jwt_randomness, eph_pubkey, salt),
+        invalid("max_epoch")
+    );
+    assert_eq!(
+        validate_proof_request(&jwt, 10, "0x1234", eph_pubkey, salt),
+        invalid("jwt_randomness")
+    );
+    assert_eq!(
+        validate_proof_request(
+            &jwt,
+            10,
+            jwt_randomness,
+            eph_pubkey,
+            "0248191903847969014646285995941615069143"
+        ),
+        invalid("salt")
+    );
+    for eph_pubkey in [
+        "",
+        "0",
+        "12345",
+        "-84029355920633174015103288781128426107680789454168570548782290541079926444544",
+    ] {
+        assert_eq!(
+            validate_proof_request(&jwt, 10, jwt_randomness, eph_pubkey, salt),
+            invalid("eph_pubkey")
+        );
+    }
+}
+
+#[test]
+fn test_split_to_two_frs() {
+    let (first, second) = split_to_two_frs(&[1; 33]).unwrap();
+    assert_eq!(first, Bn254Fr::from(BigUint::from_bytes_be(&[1; 17])));
+    assert_eq!(second, Bn254Fr::from(BigUint::from_bytes_be(&[1; 16])));
+    assert!(split_to_two_frs(&[1; 34]).is_ok());
+    assert!(split_to_two_frs(&[0xff; 47]).is_ok());
+
+    assert_eq!(
+        split_to_two_frs(&[]),
+        Err(FastCryptoError::InputLengthWrong {
+            expected: 17,
+            actual: 0,
+        })
+    );
+    assert_eq!(
+        split_to_two_frs(&[1; 16]),
+        Err(FastCryptoError::InputLengthWrong {
+            expected: 17,
+            actual: 16,
+        })
+    );
+    assert_eq!(
+        split_to_two_frs(&[1; 48]),
+        Err(FastCryptoError::InputLengthWrong {
+            expected: 47,
+            actual: 48,
+        })
+    );
+    assert_eq!(
+        get_nonce(&[1; 16], 10, "100681567828351849884072155819400689117"),
+        Err(FastCryptoError::InputLengthWrong {
+            expected: 17,
+            actual: 16,
+        })
+    );
+}
+
+#[test]
+fn test_get_nonce() {
+    let kp = Ed25519KeyPair::generate(&mut StdRng::from_seed([0; 32]));
+    let mut eph_pk_bytes = vec![0x00];
+    eph_pk_bytes.extend(kp.public().as_ref());
+    let nonce = get_nonce(&eph_pk_bytes, 10, "100681567828351849884072155819400689117").unwrap();
+    assert_eq!(nonce, "hTPpgF7XAKbW37rEUS6pEVZqmoI");
+}
+
+#[test]
+fn test_get_nonce_full() {
+    let eph_pk_bytes = ed25519_eph_pk_bytes();
+    let jwt_randomness = "100681567828351849884072155819400689117";
+
+    let (hash, nonce) = get_nonce_full(&eph_pk_bytes, 10, jwt_randomness).unwrap();
+    assert_eq!(nonce, "hTPpgF7XAKbW37rEUS6pEVZqmoI");
+    assert_eq!(Base64UrlUnpadded::decode_vec(&nonce).unwrap(), hash[12..]);
+    assert_eq!(
+        Base64UrlUnpadded::encode_string(&hash),
+        get_nonce_with_policy(&eph_pk_bytes, 10, jwt_randomness, NoncePolicy::Full32).unwrap()
+    );
+}
+
+#[test]
+fn test_get_nonce_with_policy() {
+    let eph_pk_bytes = ed25519_eph_pk_bytes();
+    let jwt_randomness = "100681567828351849884072155819400689117";
+    let nonce = |policy| get_nonce_with_policy(&eph_pk_bytes, 10, jwt_randomness, policy);
+
+    // The default policy matches get_nonce.
+    assert_eq!(NoncePolicy::default(), NoncePolicy::Truncate20);
+    assert_eq!(
+        nonce(NoncePolicy::Truncate20).unwrap(),
+        "hTPpgF7XAKbW37rEUS6pEVZqmoI"
+    );
+    assert_eq!(
+        nonce(NoncePolicy::TruncateN(20)).unwrap(),
+        "hTPpgF7XAKbW37rEUS6pEVZqmoI"
+    );
+
+    // Every policy keeps the last bytes of the same hash.
+    let full = Base64UrlUnpadded::decode_vec(&nonce(NoncePolicy::Full32).unwrap()).unwrap();
+    assert_eq!(full.len(), 32);
+    for n in 1..=32 {
+        let truncated =
+            Base64UrlUnpadded::decode_vec(&nonce(NoncePolicy::TruncateN(n)).unwrap()).unwrap();
+        assert_eq!(truncated, full[32 - n..]);
+    }
+
+    assert!(nonce(NoncePolicy::TruncateN(0)).is_err());
+    assert!(nonce(NoncePolicy::TruncateN(33)).is_err());
+}
+
+#[test]
+fn test_derive_new_session() {
+    let address =
+        hex::decode("1c6b623a2f2c91333df730c98d220f11484953b391a3818680f922c264cc0c6b").unwrap();
+    let address: [u8; 32] = address.try_into().unwrap();
+    let jwt_randomness = "100681567828351849884072155819400689117";
+
+    let eph_pk_bytes = ed25519_eph_pk_bytes();
+    let nonce = derive_new_session(address, &eph_pk_bytes, 10, jwt_randomness).unwrap();
+    assert_eq!(nonce, "hTPpgF7XAKbW37rEUS6pEVZqmoI");
+
+    // A new ephemeral key gives a new nonce for the same address.
+    let kp = Ed25519KeyPair::generate(&mut StdRng::from_seed([1; 32]));
+    let mut new_eph_pk_bytes = vec![0x00];
+    new_eph_pk_bytes.extend(kp.public().as_ref());
+    let new_nonce = derive_new_session(address, &new_eph_pk_bytes, 20, jwt_randomness).unwrap();
+    assert_ne!(new_nonce, nonce);
+    assert_eq!(
+        new_nonce,
+        get_nonce(&new_eph_pk_bytes, 20, jwt_randomness).unwrap()
+    );
+}
+
+#[test]
+fn test_get_nonce_max_epoch_u64_max() {
+    let eph_pk_bytes = ed25519_eph_pk_bytes();
+    let jwt_randomness = "100681567828351849884072155819400689117";
+
+    // u64::MAX fits in the scalar field without reduction.
+    assert_eq!(
+        Bn254Fr::from(u64::MAX),
+        Bn254Fr::from_str("18446744073709551615").unwrap()
+    );
+
+    let nonce = get_nonce(&eph_pk_bytes, u64::MAX, jwt_randomness).unwrap();
+    assert_eq!(nonce.len(), 27);
+    assert_ne!(
+        nonce,
+        get_nonce(&eph_pk_bytes, u64::MAX - 1, jwt_randomness).unwrap()
+    );
+    assert_eq!(
+        gen_nonces_for_epoch_range(&eph_pk_bytes, u64::MAX - 1..u64::MAX, jwt_randomness).unwrap(),
+        vec![(
+            u64::MAX - 1,
+            get_nonce(&eph_pk_bytes, u64::MAX - 1, jwt_randomness).unwrap()
+        )]
+    );
+}
+
+#[test]
+fn test_gen_nonces_for_epoch_range() {
+    let eph_pk_bytes = ed25519_eph_pk_bytes();
+    let jwt_randomness = "100681567828351849884072155819400689117";
+
+    let nonces = gen_nonces_for_epoch_range(&eph_pk_bytes, 8..12, jwt_randomness).unwrap();
+    assert_eq!(nonces.len(), 4);
+    for (i, (epoch, nonce)) in nonces.iter().enumerate() {
+        assert_eq!(*epoch, 8 + i as u64);
+        assert_eq!(
+            *nonce,
+            get_nonce(&eph_pk_bytes, *epoch, jwt_randomness).unwrap()
+        );
+    }
+    assert_eq!(nonces[2].1, "hTPpgF7XAKbW37rEUS6pEVZqmoI");
+
+    // Empty and reversed ranges.
+    assert!(
+        gen_nonces_for_epoch_range(&eph_pk_bytes, 10..10, jwt_randomness)
+            .unwrap()
+            .is_empty()
+    );
+    #[allow(clippy::reversed_empty_ranges)]
+    let reversed = 12..8;
+    assert!(
+        gen_nonces_for_epoch_range(&eph_pk_bytes, reversed, jwt_randomness)
+            .unwrap()
+            .is_empty()
+    );
+
+    assert!(gen_nonces_for_epoch_range(&eph_pk_bytes, 8..12, "invalid").is_err());
+}
+
+#[test]
+fn test_gen_jwt_randomness() {
+    let mut rng = StdRng::from_seed([0; 32]);
+    let jwt_randomness = gen_jwt_randomness(&mut rng);
+    // The randomness is a canonical decimal encoding of a field element.
+    let element = Bn254FrElement::from_str(&jwt_randomness).unwrap();
+    assert_eq!(element.to_string(), jwt_randomness);
+    assert!(get_nonce(&[0; 33], 10, &jwt_randomness).is_ok());
+
+    // The same seed gives the same randomness and the next sample differs.
+    assert_eq!(
+        jwt_randomness,
+        gen_jwt_randomness(&mut StdRng::from_seed([0; 32]))
+    );
+    assert_ne!(jwt_randomness, gen_jwt_randomness(&mut rng));
+}
+
+#[test]
+fn test_verify_nonce() {
+    let eph_pk_bytes = ed25519_eph_pk_bytes();
+    let jwt_randomness = "100681567828351849884072155819400689117";
+
+    let verify = |nonce_claim, max_epoch| {
+        verify_nonce(nonce_claim, &eph_pk_bytes, max_epoch, jwt_randomness)
+    };
+
+    // Both the unpadded and the padded nonce claim match.
+    assert!(verify("hTPpgF7XAKbW37rEUS6pEVZqmoI", 10).is_ok());
+    assert!(verify("hTPpgF7XAKbW37rEUS6pEVZqmoI=", 10).is_ok());
+
+    // Too much padding, a different nonce or different parameters do not match.
+    assert!(verify("hTPpgF7XAKbW37rEUS6pEVZqmoI==", 10).is_err());
+    assert!(verify("hTPpgF7XAKbW37rEUS6pEVZqmoI===", 10).is_err());
+    assert!(verify("hTPpgF7XAKbW37rEUS6pEVZqmoJ", 10).is_err());
+    assert!(verify("hTPpgF7XAKbW37rEUS6pEVZqmoI", 11).is_err());
+}
+
+#[test]
+fn test_hash_to_nonce_bytes() {
+    // The least significant bytes of the big-endian encoding are kept.
+    let mut expected = [0; NONCE_BYTES_LENGTH];
+    expected[NONCE_BYTES_LENGTH - 1] = 1;
+    assert_eq!(hash_to_nonce_bytes(Bn254Fr::from(1u64)), expected);
+    let high = Bn254Fr::from(BigUint::from(1u8) << (8 * NONCE_BYTES_LENGTH));
+    assert_eq!(hash_to_nonce_bytes(high + Bn254Fr::from(1u64)), expected);
+
+    // Known answer from the nonce in [test_verify_nonce].
+    let eph_pk_bytes = ed25519_eph_pk_bytes();
+    let (first, second) = split_to_two_frs(&eph_pk_bytes).unwrap();
+    let hash = poseidon_zk_login(&[
+        first,
+        second,
+        Bn254Fr::from(10u64),
+        Bn254Fr::from_str("100681567828351849884072155819400689117").unwrap(),
+    ])
+    .unwrap();
+    assert_eq!(
+        hash_to_nonce_bytes(hash).to_vec(),
+        Base64UrlUnpadded::decode_vec("hTPpgF7XAKbW37rEUS6pEVZqmoI").unwrap()
+    );
+}
+
+#[test]
+fn test_verify_jwt_nonce() {
+    let eph_pk_bytes = ed25519_eph_pk_bytes();
+    let jwt_randomness = "100681567828351849884072155819400689117";
+
+    let verify = |jwt: &str, nonce_pointer| {
+        verify_jwt_nonce(jwt, nonce_pointer, &eph_pk_bytes, 10, jwt_randomness)
+    };
+
+    let jwt = jwt_with_payload(serde_json::json!({"nonce": "hTPpgF7XAKbW37rEUS6pEVZqmoI"}));
+    assert!(verify(&jwt, DEFAULT_NONCE_POINTER).is_ok());
+    assert!(verify(&jwt, "/claims/nonce").is_err());
+
+    let jwt =
+        jwt_with_payload(serde_json::json!({"claims": {"nonce": "hTPpgF7XAKbW37rEUS6pEVZqmoI"}}));
+    assert!(verify(&jwt, "/claims/nonce").is_ok());
+    assert_eq!(
+        verify(&jwt, DEFAULT_NONCE_POINTER),
+        Err(FastCryptoError::GeneralError(
+            "Missing nonce at /nonce".to_string()
+        ))
+    );
+    assert_eq!(
+        verify(&jwt, "/claims"),
+        Err(FastCryptoError::GeneralError(
+            "Nonce at /claims is not a string".to_string()
+        ))
+    );
+
+    // A nonce for different parameters does not match.
+    let jwt = jwt_with_payload(serde_json::json!({"nonce": "hTPpgF7XAKbW37rEUS6pEVZqmoJ"}));
+    assert!(verify(&jwt, DEFAULT_NONCE_POINTER).is_err());
+}
+
+#[test]
+fn test_verify_max_epoch_consistency() {
+    let reader: ZkLoginInputsReader = serde_json::from_str(GOOGLE_ALT_ISS_PROOF).unwrap();
+    let eph_pk_bytes = ed25519_eph_pk_bytes();
+    let jwt_randomness = "100681567828351849884072155819400689117";
+
+    // The nonce from [test_verify_nonce] commits to max_epoch 10.
+    let payload = Base64UrlUnpadded::encode_string(br#"{"nonce":"hTPpgF7XAKbW37rEUS6pEVZqmoI"}"#);
+    let jwt = format!("{}.{}.signature", reader.get_header_base64(), payload);
+    assert_eq!(
+        verify_max_epoch_consistency(&jwt, &reader, 10, &eph_pk_bytes, jwt_randomness),
+        Ok(())
+    );
+    assert!(
+        verify_max_epoch_consistency(&jwt, &reader, 11, &eph_pk_bytes, jwt_randomness).is_err()
+    );
+
+    // A JWT with a different header is rejected.
+    let jwt = jwt_with_payload(serde_json::json!({"nonce": "hTPpgF7XAKbW37rEUS6pEVZqmoI"}));
+    assert_eq!(
+        verify_max_epoch_consistency(&jwt, &reader, 10, &eph_pk_bytes, jwt_randomness),
+        Err(FastCryptoError::GeneralError(
+            "The proof was not generated for this JWT".to_string()
+        ))
+    );
+}
+
+#[test]
+fn test_verify_proof_bundle() {
+    // Proof from [test_alternative_iss_for_google] and claims from [test_candidate_addresses].
+    let reader = ZkLoginInputsReader::dummy();
+    let eph_pk_bytes = ed25519_eph_pk_bytes();
+    let jwt_randomness = "100681567828351849884072155819400689117";
+    let salt = "6588741469050502421550140105345050859";
+    let address: [u8; 32] =
+        hex::decode("1c6b623a2f2c91333df730c98d220f11484953b391a3818680f922c264cc0c6b")
+            .unwrap()
+            .try_into()
+            .unwrap();
+
+    let jwt = |iss: &str, aud: serde_json::Value| {
+        let payload = serde_json::json!({
+            "iss": iss,
+            "aud": aud,
+            "sub": "106294049240999307923",
+            "nonce": "hTPpgF7XAKbW37rEUS6pEVZqmoI",
+        });
+        format!(
+            "{}.{}.signature",
+            reader.get_header_base64(),
+            Base64UrlUnpadded::encode_string(payload.to_string().as_bytes())
+        )
+    };
+    let aud = serde_json::json!(
+        "575519204237-msop9ep45u2uo98hapqmngv8d84qdc8k.apps.googleusercontent.com"
+    );
+    let valid = jwt("https://accounts.google.com", aud.clone());
+    assert_eq!(
+        verify_proof_bundle(&valid, salt, &eph_pk_bytes, 10, jwt_randomness, &reader),
+        Ok(address)
+    );
+
+    // The nonce must commit to the ephemeral parameters.
+    assert!(verify_proof_bundle(&valid, salt, &eph_pk_bytes, 11, jwt_randomness, &reader).is_err());
+    assert!(verify_proof_bundle(&valid, salt, &eph_pk_bytes, 10, "1", &reader).is_err());
+
+    // The proof must commit to the iss of the JWT.
+    let other_iss = jwt("https://www.facebook.com", aud.clone());
+    assert_eq!(
+        verify_proof_bundle(&other_iss, salt, &eph_pk_bytes, 10, jwt_randomness, &reader),
+        Err(FastCryptoError::GeneralError(
+            "Expected iss https://www.facebook.com but the proof has iss https://accounts.google.com"
+                .to_string()
+        ))
+    );
+
+    // The address is only defined for a single audience.
+    let two_auds = jwt(
+        "https://accounts.google.com",
+        serde_json::json!(["other_client_id", aud]),
+    );
+    assert!(
+        verify_proof_bundle(&two_auds, salt, &eph_pk_bytes, 10, jwt_randomness, &reader).is_err()
+    );
+
+    // A proof for another JWT is rejected.
+    let other_header = jwt_with_payload(serde_json::json!({}));
+    let other_header = format!(
+        "{}.{}",
+        other_header.split('.').next().unwrap(),
+        valid.split_once('.').unwrap().1
+    );
+    assert!(verify_proof_bundle(
+        &other_header,
+        salt,
+        &eph_pk_bytes,
+        10,
+        jwt_randomness,
+        &reader
+    )
+    .is_err());
+}
+
+#[cfg(feature = "client")]
+#[test]
+fn test_validate_redirect_uri() {
+    let google = OIDCProvider::Google;
+    assert!(validate_redirect_uri(&google, "https://sui.io/callback").is_ok());
+    assert!(validate_redirect_uri(&google, "https://sui.io/callback?x=1").is_ok());
+    assert!(validate_redirect_uri(&google, "http://localhost:3000/callback").is_ok());
+    assert!(validate_redirect_uri(&google, "http://127.0.0.1:3000").is_ok());
+
+    // Plain http, fragments, wildcards and relative URLs are rejected.
+    assert!(validate_redirect_uri(&google, "http://sui.io/callback").is_err());
+    assert!(validate_redirect_uri(&google, "https://sui.io/callback#token").is_err());
+    assert!(validate_redirect_uri(&google, "https://*.sui.io/callback").is_err());
+    assert!(validate_redirect_uri(&google, "/callback").is_err());
+    assert!(validate_redirect_uri(&google, "").is_err());
+
+    // Apple does not allow plain http even for localhost.
+    assert!(validate_redirect_uri(&OIDCProvider::Apple, "http://localhost:3000").is_err());
+    assert!(validate_redirect_uri(&OIDCProvider::Apple, "https://sui.io/callback").is_ok());
+}
+
+#[cfg(feature = "client")]
+#[test]
+fn test_zk_login_config_validate() {
+    let config = ZkLoginConfig {
+        provider: "Google".to_string(),
+        client_id: "client_id".to_string(),
+        redirect_url: "https://sui.io/callback".to_string(),
+        salt_url: "https://salt.api.mystenlabs.com/get_salt".to_string(),
+        prover_url: "http://localhost:8080/v1".to_string(),
+    };
+    assert!(config.validate().is_ok());
+
+    // All problems are reported.
+    let invalid = ZkLoginConfig {
+        provider: "Unknown".to_string(),
+        client_id: "".to_string(),
+        redirect_url: "http://sui.io/callback".to_string(),
+        salt_url: "not a url".to_string(),
+        prover_url: "ftp://prover.sui.io".to_string(),
+    };
+    assert_eq!(
+        invalid.validate().unwrap_err(),
+        vec![
+            FastCryptoError::GeneralError("Unsupported provider Unknown".to_string()),
+            FastCryptoError::GeneralError("Invalid client ID \"\"".to_string()),
+            FastCryptoError::GeneralError("Invalid salt URL not a url".to_string()),
+            FastCryptoError::GeneralError("Invalid prover URL ftp://prover.sui.io".to_string()),
+        ]
+    );
+
+    // The redirect URI is checked against the provider.
+    let apple = ZkLoginConfig {
+        provider: "Apple".to_string(),
+        redirect_url: "http://localhost:3000".to_string(),
+        ..config
+    };
+    assert_eq!(apple.validate().unwrap_err().len(), 1);
+}
+
+#[cfg(feature = "client")]
+#[test]
+fn test_get_oidc_url_validates_redirect_uri() {
+    let eph_pk_bytes = ed25519_eph_pk_bytes();
+    let jwt_randomness = "100681567828351849884072155819400689117";
+
+    let url = get_oidc_url(
+        OIDCProvider::Google,
+        &eph_pk_bytes,
+        10,
+        "client_id",
+        "https://sui.io/callback",
+        jwt_randomness,
+    )
+    .unwrap();
+    assert!(url.contains("nonce=hTPpgF7XAKbW37rEUS6pEVZqmoI"));
+
+    assert!(get_oidc_url(
+        OIDCProvider::Google,
+        &eph_pk_bytes,
+        10,
+        "client_id",
+        "http://sui.io/callback",
+        jwt_randomness,
+    )
+    .is_err());
+}
+
+#[test]
+fn test_get_provider_to_from_iss_to_from_str() {
+    for p in [
+        OIDCProvider::Facebook,
+        OIDCProvider::Google,
+        OIDCProvider::Twitch,
+        OIDCProvider::Slack,
+        OIDCProvider::Kakao,
+        OIDCProvider::Apple,
+        OIDCProvider::Microsoft,
+        OIDCProvider::AwsTenant(("us-east-1".to_string(), "us-east-1_LPSLCkC3A".to_string())),
+        OIDCProvider::AwsTenant(("us-east-1".to_string(), "us-east-1_qPsZxYqd8".to_string())),
+        OIDCProvider::KarrierOne,
+        OIDCProvider::Credenza3,
+        OIDCProvider::Playtron,
+        OIDCProvider::Threedos,
+        OIDCProvider::Onefc,
+        OIDCProvider::FanTV,
+    ] {
+        // to/from iss
+        assert_eq!(p, OIDCProvider::from_iss(&p.get_config().iss).unwrap());
+        // to/from string
+        assert_eq!(p, OIDCProvider::from_str(&p.to_string()).unwrap());
+    }
+    assert!(OIDCProvider::from_iss("random").is_err());
+    assert!(OIDCProvider::from_str("random").is_err());
+}
+
+/// Build an unsigned JWT with the given payload.
+fn jwt_with_payload(payload: serde_json::Value) -> String {
+    format!(
+        "{}.{}.signature",
+        Base64UrlUnpadded::encode_string(br#"{"alg":"RS256","kid":"kid","typ":"JWT"}"#),
+        Base64UrlUnpadded::encode_string(payload.to_string().as_bytes())
+    )
+}
+
+#[test]
+fn test_jwk_cache_key() {
+    let kid = "6f7254101f56e41cf35c9926de84a2d552b4c6f1";
+    assert_eq!(
+        jwk_cache_key(OIDCProvider::Google, kid),
+        "google:6f7254101f56e41cf35c9926de84a2d552b4c6f1"
+    );
+    assert_ne!(
+        jwk_cache_key(OIDCProvider::Google, kid),
+        jwk_cache_key(OIDCProvider::Twitch, kid)
+    );
+
+    // Tenant IDs are case-sensitive and kept verbatim.
+    let tenant =
+        |tenant_id: &str| OIDCProvider::AwsTenant(("us-east-1".to_string(), tenant_id.to_string()));
+    assert_eq!(
+        jwk_cache_key(tenant("us-east-1_LPSLCkC3A"), kid),
+        "awstenant/us-east-1/us-east-1_LPSLCkC3A:6f7254101f56e41cf35c9926de84a2d552b4c6f1"
+    );
+    assert_ne!(
+        jwk_cache_key(tenant("us-east-1_LPSLCkC3A"), kid),
+        jwk_cache_key(tenant("us-east-1_lpslckc3a"), kid)
+    );
+
+    // The key is split at the first colon, even if the kid or the tenant contains one.
+    let key = jwk_cache_key(OIDCProvider::Google, "a:b");
+    assert_eq!(key.split_once(':'), Some(("google", "a:b")));
+    let key = jwk_cache_key(tenant("x:y"), "a:b");
+    assert_eq!(
+        key.split_once(':'),
+        Some(("awstenant/us-east-1/x%3Ay", "a:b"))
+    );
+    assert_ne!(
+        jwk_cache_key(tenant("x"), "y:a"),
+        jwk_cache_key(tenant("x:y"), "a")
+    );
+
+    let (_, jwk) = google_alt_iss_jwk();
+    let key = jwk_content_cache_key(OIDCProvider::Google, &jwk);
+    assert!(key.starts_with("google:"));
+    assert_eq!(key.len(), "google:".len() + 64);
+
+    // Only n and e are hashed.
+    let other_alg = JWK {
+        alg: "RS512".to_string(),
+        ..jwk.clone()
+    };
+    assert_eq!(jwk_content_cache_key(OIDCProvider::Google, &other_alg), key);
+    let other_e = JWK {
+        e: "AQAC".to_string(),
+        ..jwk.clone()
+    };
+    assert_ne!(jwk_content_cache_key(OIDCProvider::Google, &other_e), key);
+    assert_ne!(jwk_content_cache_key(OIDCProvider::Apple, &jwk), key);
+}
+
+#[test]
+fn test_check_authorized_party() {
+    let azp = "575519204237-msop9ep45u2uo98hapqmngv8d84qdc8k.apps.googleusercontent.com";
+    let jwt = jwt_with_payload(serde_json::json!({
+        "iss": "https://accounts.google.com",
+        "azp": azp,
+        "aud": azp,
+    }));
+    assert!(check_authorized_party(&jwt, azp).is_ok());
+    assert_eq!(
+        check_authorized_party(&jwt, "other"),
+        Err(FastCryptoError::GeneralError(format!(
+            "Expected azp other but the JWT has azp {}",
+            azp
+        )))
+    );
+
+    let jwt = jwt_with_payload(serde_json::json!({ "aud": azp }));
+    assert_eq!(
+        check_authorized_party(&jwt, azp),
+        Err(FastCryptoError::GeneralError(
+            "Missing claim azp".to_string()
+        ))
+    );
+}
+
+#[test]
+fn test_provider_from_jwt() {
+    let provider =
+        |iss: &str| provider_from_jwt(&jwt_with_payload(serde_json::json!({ "iss": iss })));
+
+    assert_eq!(
+        provider("https://accounts.google.com"),
+        Ok(OIDCProvider::Google)
+    );
+    assert_eq!(
+        provider("https://cognito-idp.us-east-1.amazonaws.com/us-east-1_LPSLCkC3A"),
+        Ok(OIDCProvider::AwsTenant((
+            "us-east-1".to_string(),
+            "us-east-1_LPSLCkC3A".to_string()
+        )))
+    );
+    assert_eq!(
+        provider("https://random.com"),
+        Err(FastCryptoError::InvalidInput)
+    );
+
+    // The iss claim must be present and a string.
+    assert!(provider_from_jwt(&jwt_with_payload(serde_json::json!({ "sub": "1234" }))).is_err());
+    assert!(provider_from_jwt(&jwt_with_payload(serde_json::json!({ "iss": 1 }))).is_err());
+    assert!(provider_from_jwt("not a jwt").is_err());
+}
+
+#[test]
+fn test_extract_address_claims() {
+    let jwt = jwt_with_payload(serde_json::json!({
+        "iss": "https://accounts.google.com",
+        "aud": "client_id",
+        "sub": "1234",
+        "email": "user@example.com",
+    }));
+    assert_eq!(
+        parse_jwt_payload(&jwt).unwrap().get("sub"),
+        Some(&serde_json::json!("1234"))
+    );
+    assert_eq!(
+        extract_address_claims(&jwt, KeyClaimName::Sub).unwrap(),
+        AddressClaims {
+            iss: "https://accounts.google.com".to_string(),
+            aud: "client_id".to_string(),
+            key_value: "1234".to_string(),
+        }
+    );
+    assert_eq!(
+        extract_address_claims(&jwt, KeyClaimName::Email)
+            .unwrap()
+            .key_value,
+        "user@example.com"
+    );
+
+    // A single element aud array is accepted, multiple audiences are not.
+    let jwt = jwt_with_payload(serde_json::json!({
+        "iss": "https://accounts.google.com",
+        "aud": ["client_id"],
+        "sub": "1234",
+    }));
+    assert_eq!(
+        extract_address_claims(&jwt, KeyClaimName::Sub).unwrap().aud,
+        "client_id"
+    );
+    let jwt = jwt_with_payload(serde_json::json!({
+        "iss": "https://accounts.google.com",
+        "aud": ["client_id", "other_client_id"],
+        "sub": "1234",
+    }));
+    assert!(extract_address_claims(&jwt, KeyClaimName::Sub).is_err());
+
+    // Missing and non-string claims.
+    let jwt = jwt_with_payload(serde_json::json!({
+        "iss": "https://accounts.google.com",
+        "aud": "client_id",
+        "sub": 1234,
+    }));
+    assert_eq!(
+        extract_address_claims(&jwt, KeyClaimName::Sub),
+        Err(FastCryptoError::GeneralError(
+            "Claim sub is a number but only string key claims are supported".to_string()
+        ))
+    );
+    assert_eq!(
+        extract_address_claims(&jwt, KeyClaimName::Email),
+        Err(FastCryptoError::GeneralError(
+            "Missing claim email".to_string()
+        ))
+    );
+
+    // Malformed tokens.
+    assert!(parse_jwt_payload("header.payload").is_err());
+    assert!(parse_jwt_payload("header.!!!.signature").is_err());
+}
+
+#[test]
+fn test_key_claim_value() {
+    let salt = "6588741469050502421550140105345050859";
+    let string_sub = jwt_with_payload(serde_json::json!({
+        "iss": "https://accounts.google.com",
+        "aud": "client_id",
+        "sub": "106294049240999307923",
+    }));
+    let payload = parse_jwt_payload(&string_sub).unwrap();
+    assert_eq!(
+        key_claim_value(&payload, "sub").unwrap(),
+        "106294049240999307923"
+    );
+    assert_eq!(
+        candidate_addresses(&string_sub, salt).unwrap()[0].1,
+        address_from_claims(
+            "https://accounts.google.com",
+            "client_id",
+            "106294049240999307923",
+            6588741469050502421550140105345050859
+        )
+    );
+
+    // A numeric sub is rejected rather than formatted as the string above, both for integers that
+    // fit in a u64 and for those that do not.
+    for sub in [
+        serde_json::json!(1234),
+        serde_json::from_str("106294049240999307923").unwrap(),
+    ] {
+        let numeric_sub = jwt_with_payload(serde_json::json!({
+            "iss": "https://accounts.google.com",
+            "aud": "client_id",
+            "sub": sub,
+        }));
+        let expected = FastCryptoError::GeneralError(
+            "Claim sub is a number but only string key claims are supported".to_string(),
+        );
+        assert_eq!(
+            key_claim_value(&parse_jwt_payload(&numeric_sub).unwrap(), "sub"),
+            Err(expected.clone())
+        );
+        assert_eq!(candidate_addresses(&numeric_sub, salt), Err(expected));
+    }
+
+    let bool_sub = jwt_with_payload(serde_json::json!({ "sub": true }));
+    assert_eq!(
+        key_claim_value(&parse_jwt_payload(&bool_sub).unwrap(), "sub"),
+        Err(FastCryptoError::GeneralError(
+            "Claim sub is not a string".to_string()
+        ))
+    );
+}
+
+#[test]
+fn test_check_aud_matches() {
+    let jwt = jwt_with_payload(serde_json::json!({ "aud": "client_id" }));
+    assert!(check_aud_matches(&jwt, "client_id").is_ok());
+    assert_eq!(
+        check_aud_matches(&jwt, "other_client_id"),
+        Err(FastCryptoError::GeneralError(
+            "Expected aud other_client_id but the JWT has aud [\"client_id\"]".to_string()
+        ))
+    );
+
+    let jwt = jwt_with_payload(serde_json::json!({ "aud": ["client_id", "other_client_id"] }));
+    assert!(check_aud_matches(&jwt, "client_id").is_ok());
+    assert!(check_aud_matches(&jwt, "other_client_id").is_ok());
+    assert!(check_aud_matches(&jwt, "third_client_id").is_err());
+
+    let jwt = jwt_with_payload(serde_json::json!({ "sub": "1234" }));
+    assert!(check_aud_matches(&jwt, "client_id").is_err());
+}
+
+#[test]
+fn test_check_hosted_domain() {
+    let jwt = jwt_with_payload(serde_json::json!({
+        "iss": "https://accounts.google.com",
+        "hd": "example.com",
+    }));
+    assert!(check_hosted_domain(&jwt, "example.com").is_ok());
+    assert_eq!(
+        check_hosted_domain(&jwt, "other.com"),
+        Err(FastCryptoError::InvalidInput)
+    );
+    assert_eq!(
+        check_hosted_domain(&jwt, "Example.com"),
+        Err(FastCryptoError::InvalidInput)
+    );
+
+    // Personal Google accounts have no hd claim.
+    let jwt = jwt_with_payload(serde_json::json!({ "iss": "https://accounts.google.com" }));
+    assert_eq!(
+        check_hosted_domain(&jwt, "example.com"),
+        Err(FastCryptoError::InvalidInput)
+    );
+}
+
+#[test]
+fn test_candidate_addresses() {
+    // Test vector from [test_verify_zk_login]
+    let iss = "https://accounts.google.com";
+    let sub = "106294049240999307923";
+    let aud = "575519204237-msop9ep45u2uo98hapqmngv8d84qdc8k.apps.googleusercontent.com";
+    let salt = "6588741469050502421550140105345050859";
+    let address: [u8; 32] =
+        hex::decode("1c6b623a2f2c91333df730c98d220f11484953b391a3818680f922c264cc0c6b")
+            .unwrap()
+            .try_into()
+            .unwrap();
+
+    let jwt = jwt_with_payload(serde_json::json!({ "iss": iss, "aud": aud, "sub": sub }));
+    assert_eq!(
+        candidate_addresses(&jwt, salt).unwrap(),
+        vec![(aud.to_string(), address)]
+    );
+
+    let jwt = jwt_with_payload(serde_json::json!({
+        "iss": iss,
+        "aud": ["other_client_id", aud],
+        "sub": sub,
+    }));
+    let candidates = candidate_addresses(&jwt, salt).unwrap();
+    assert_eq!(candidates.len(), 2);
+    assert_eq!(candidates[0].0, "other_client_id");
+    assert_ne!(candidates[0].1, address);
+    assert_eq!(candidates[1], (aud.to_string(), address));
+
+    let jwt = jwt_with_payload(serde_json::json!({ "iss": iss, "aud": aud }));
+    assert!(candidate_addresses(&jwt, salt).is_err());
+}
+
+#[test]
+fn test_provider_as_map_key() {
+    let aws_1 =
+        OIDCProvider::AwsTenant(("us-east-1".to_string(), "us-east-1_LPSLCkC3A".to_string()));
+    let aws_2 =
+        OIDCProvider::AwsTenant(("us-east-1".to_string(), "us-east-1_qPsZxYqd8".to_string()));
+    let providers = [
+        OIDCProvider::Twitch,
+        aws_2.clone(),
+        OIDCProvider::Google,
+        aws_1.clone(),
+        OIDCProvider::FanTV,
+    ];
+
+    let sorted: Vec<_> = providers
+        .iter()
+        .cloned()
+        .collect::<std::collections::BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    assert_eq!(
+        sorted,
+        vec![
+            OIDCProvider::Google,
+            OIDCProvider::Twitch,
+            aws_1.clone(),
+            aws_2.clone(),
+            OIDCProvider::FanTV
+        ]
+    );
+
+    let mut map = std::collections::HashMap::new();
+    for (i, p) in providers.iter().enumerate() {
+        map.insert(p.clone(), i);
+    }
+    assert_eq!(map.len(), providers.len());
+    assert_eq!(map[&aws_1], 3);
+    assert_eq!(map[&aws_2], 1);
+}
+
+#[test]
+fn test_gen_seed() {
+    let address_seed = gen_address_seed(
+        "248191903847969014646285995941615069143",
+        "sub",
+        "904448692",
+        "rs1bh065i9ya4ydvifixl4kss0uhpt",
+    )
+    .unwrap();
+    assert_eq!(
+        address_seed.to_string(),
+        "16657007263003735230240998439420301694514420923267872433517882233836276100450".to_string()
+    );
+}
+
+#[test]
+fn test_zk_login_address_preimage() {
+    // Test vector from [test_verify_zk_login]
+    let address_seed = Bn254FrElement::from_str(
+        &gen_address_seed(
+            "6588741469050502421550140105345050859",
+            "sub",
+            "106294049240999307923",
+            "575519204237-msop9ep45u2uo98hapqmngv8d84qdc8k.apps.googleusercontent.com",
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    let iss = "https://accounts.google.com";
+
+    let preimage = zk_login_address_preimage(&address_seed, iss).unwrap();
+    assert_eq!(preimage.len(), 2 + iss.len() + 32);
+    assert_eq!(preimage[0], 0x05);
+    assert_eq!(preimage[1] as usize, iss.len());
+    assert_eq!(&preimage[2..2 + iss.len()], iss.as_bytes());
+    assert_eq!(&preimage[2 + iss.len()..], address_seed.padded());
+
+    let address = get_zk_login_address(&address_seed, iss).unwrap();
+    assert_eq!(Blake2b256::digest(&preimage).digest, address);
+    assert_eq!(
+        address.to_vec(),
+        hex::decode("1c6b623a2f2c91333df730c98d220f11484953b391a3818680f922c264cc0c6b").unwrap()
+    );
+}
+
+#[test]
+fn test_zk_login_address_eq() {
+    // Address from [test_verify_zk_login]
+    let hex = "1c6b623a2f2c91333df730c98d220f11484953b391a3818680f922c264cc0c6b";
+    let address: [u8; 32] = hex::decode(hex).unwrap().try_into().unwrap();
+
+    assert!(zk_login_address_eq(hex, &address).unwrap());
+    assert!(zk_login_address_eq(&format!("0x{}", hex), &address).unwrap());
+    assert!(zk_login_address_eq(&format!("0X{}", hex.to_uppercase()), &address).unwrap());
+
+    let mut other = address;
+    other[31] ^= 1;
+    assert!(!zk_login_address_eq(hex, &other).unwrap());
+
+    assert_eq!(
+        zk_login_address_eq(&hex[2..], &address),
+        Err(FastCryptoError::InputLengthWrong {
+            expected: 32,
+            actual: 31,
+        })
+    );
+    assert_eq!(
+        zk_login_address_eq(&hex.replace('c', "g"), &address),
+        Err(FastCryptoError::InvalidInput)
+    );
+}
+
+#[test]
+fn test_salt_hash() {
+    // Test vector from [test_verify_zk_login]
+    let salt = "6588741469050502421550140105345050859";
+    let hash = crate::bn254::utils::salt_hash(salt).unwrap();
+    assert_eq!(
+        Bn254Fr::from(&hash),
+        poseidon_zk_login(&[(&Bn254FrElement::from_str(salt).unwrap()).into()]).unwrap()
+    );
+
+    let (name, value, aud) = (
+        "sub",
+        "106294049240999307923",
+        "575519204237-msop9ep45u2uo98hapqmngv8d84qdc8k.apps.googleusercontent.com",
+    );
+    assert_eq!(
+        gen_address_seed(salt, name, value, aud).unwrap(),
+        gen_address_seed_with_salt_hash(
+            &hash.to_string(),
+            name,
+            value,
+            aud,
+            &CircuitParams::default()
+        )
+        .unwrap()
+    );
+
+    assert!(crate::bn254::utils::salt_hash("not a number").is_err());
+}
+
+#[test]
+fn test_circuit_params() {
+    assert_eq!(
+        CircuitParams::for_version(CURRENT_CIRCUIT_VERSION),
+        Ok(CircuitParams::default())
+    );
+    assert_eq!(
+        CircuitParams::for_version(CURRENT_CIRCUIT_VERSION + 1),
+        Err(FastCryptoError::InvalidInput)
+    );
+
+    // Test vector from [test_verify_zk_login]
+    let salt_hash = crate::bn254::utils::salt_hash("6588741469050502421550140105345050859")
+        .unwrap()
+        .to_string();
+    let (name, value, aud) = (
+        "sub",
+        "106294049240999307923",
+        "575519204237-msop9ep45u2uo98hapqmngv8d84qdc8k.apps.googleusercontent.com",
+    );
+    let address_seed =
+        gen_address_seed_with_salt_hash(&salt_hash, name, value, aud, &CircuitParams::default())
+            .unwrap();
+    assert_eq!(
+        address_seed,
+        gen_address_seed("6588741469050502421550140105345050859", name, value, aud).unwrap()
+    );
+
+    // Other widths give another address seed, and claims longer than the widths are rejected.
+    let wider = CircuitParams {
+        max_aud_value_length: 150,
+        ..CircuitParams::default()
+    };
+    assert_ne!(
+        gen_address_seed_with_salt_hash(&salt_hash, name, value, aud, &wider).unwrap(),
+        address_seed
+    );
+    let narrower = CircuitParams {
+        max_aud_value_length: 64,
+        ..CircuitParams::default()
+    };
+    assert!(gen_address_seed_with_salt_hash(&salt_hash, name, value, aud, &narrower).is_err());
+
+    // The public entry point takes the salt and the parameters.
+    let salt = "6588741469050502421550140105345050859";
+    let params = CircuitParams::for_version(CURRENT_CIRCUIT_VERSION).unwrap();
+    assert_eq!(
+        gen_address_seed_with_params(salt, name, value, aud, &params).unwrap(),
+        address_seed
+    );
+    assert_eq!(
+        gen_address_seed_with_params(salt, name, value, aud, &wider).unwrap(),
+        gen_address_seed_with_salt_hash(&salt_hash, name, value, aud, &wider).unwrap()
+    );
+    assert!(gen_address_seed_with_params(salt, name, value, aud, &narrower).is_err());
+}
+
+#[test]
+fn test_verify_zk_login() {
+    // Test vector from [test_verify_zk_login_google]
+    let address =
+        hex::decode("1c6b623a2f2c91333df730c98d220f11484953b391a3818680f922c264cc0c6b").unwrap();
+    let name = "sub";
+    let value = "106294049240999307923";
+    let aud = "575519204237-msop9ep45u2uo98hapqmngv8d84qdc8k.apps.googleusercontent.com";
+    let salt = "6588741469050502421550140105345050859";
+    let iss = "https://accounts.google.com";
+    let salt_hash = poseidon_zk_login(&[(&Bn254FrElement::from_str(salt).unwrap()).into()])
+        .unwrap()
+        .to_string();
+    assert!(verify_zk_login_id(&address, name, value, aud, iss, &salt_hash).is_ok());
+
+    let address_seed =
+        gen_address_seed_with_salt_hash(&salt_hash, name, value, aud, &CircuitParams::default())
+            .unwrap();
+    assert!(verify_zk_login_iss(&address, &address_seed, iss).is_ok());
+
+    let other_iss = "https://some.other.issuer.com";
+    assert_eq!(
+        verify_zk_login_id(&address, name, value, aud, other_iss, &salt_hash),
+        Err(FastCryptoError::InvalidProof)
+    );
+    assert_eq!(
+        verify_zk_login_iss(&address, &address_seed, other_iss),
+        Err(FastCryptoError::InvalidProof)
+    );
+
+    let too_long_kc_name = "subsubsubsubsubsubsubsubsubsubsubsub";
+    assert_eq!(
+        verify_zk_login_id(
+            &address,
+            too_long_kc_name,
+            value,
+            aud,
+            other_iss,
+            &salt_hash
+        ),
+        Err(FastCryptoError::GeneralError("in_arr too long".to_string()))
+    );
+
+    let too_long_kc_value = "106294049240999307923106294049240999307923106294049240999307923106294049240999307923106294049240999307923106294049240999307923";
+    assert_eq!(
+        verify_zk_login_id(
+            &address,
+            name,
+            too_long_kc_value,
+            aud,
+            other_iss,
+            &salt_hash
+        ),
+        Err(FastCryptoError::GeneralError("in_arr too long".to_string()))
+    );
+}
+
+#[test]
+fn test_all_inputs_hash() {
+    let jwt_sha2_hash_0 = Fr::from_str("248987002057371616691124650904415756047").unwrap();
+    let jwt_sha2_hash_1 = Fr::from_str("113498781424543581252500776698433499823").unwrap();
+    let masked_content_hash = Fr::from_str(
+        "14900420995580824499222150327925943524564997104405553289134597516335134742309",
+    )
+    .unwrap();
+    let payload_start_index = Fr::from_str("103").unwrap();
+    let payload_len = Fr::from_str("564").unwrap();
+    let eph_public_key_0 = Fr::from_str("17932473587154777519561053972421347139").unwrap();
+    let eph_public_key_1 = Fr::from_str("134696963602902907403122104327765350261").unwrap();
+    let max_epoch = Fr::from_str("10000").unwrap();
+    let num_sha2_blocks = Fr::from_str("11").unwrap();
+    let key_claim_name_f = Fr::from_str(
+        "18523124550523841778801820019979000409432455608728354507022210389496924497355",
+    )
+    .unwrap();
+    let addr_seed = Fr::from_str(
+        "15604334753912523265015800787270404628529489918817818174033741053550755333691",
+    )
+    .unwrap();
+
+    let hash = poseidon_zk_login(&[
+        jwt_sha2_hash_0,
+        jwt_sha2_hash_1,
+        masked_content_hash,
+        payload_start_index,
+        payload_len,
+        eph_public_key_0,
+        eph_public_key_1,
+        max_epoch,
+        num_sha2_blocks,
+        key_claim_name_f,
+        addr_seed,
+    ])
+    .unwrap();
+    assert_eq!(
+        hash.to_string(),
+        "2487117669597822357956926047501254969190518860900347921480370492048882803688".to_string()
+    );
+}
+#[test]
+fn test_alternative_iss_for_google() {
+    let input = ZkLoginInputs::from_json("{\"proofPoints\":{\"a\":[\"7566241567720780416751598994698310678767195459947224622023785587667176814058\",\"18104499930818305143361187733659014043953751050617136254447624192327280445771\",\"1\"],\"b\":[[\"11369230593957954942221175389182778816136534144714579815927653075736806430994\",\"11928003240637992017698644299021052465098754853899210401706726930513411198353\"],[\"2597127058046351054449743605218058440565462021354202666955356076272028963802\",\"3385145993275542896693643488618289924488296318344621918448585222369718288892\"],[\"1\",\"0\"]],\"c\":[\"395141536511114303768253959602639884294254888080713473665269769443249414257\",\"21430657725804540809568084344756144327539843580919730138594118365564728808275\",\"1\"]},\"issBase64Details\":{\"value\":\"yJpc3MiOiJodHRwczovL2FjY291bnRzLmdvb2dsZS5jb20iLC\",\"indexMod4\":1},\"headerBase64\":\"eyJhbGciOiJSUzI1NiIsImtpZCI6ImM5YWZkYTM2ODJlYmYwOWViMzA1NWMxYzRiZDM5Yjc1MWZiZjgxOTUiLCJ0eXAiOiJKV1QifQ\"}", "4959624758616676340947699768172740454110375485415332267384397278368360470616").unwrap();
+    let invalid_proof_input = ZkLoginInputs::from_json("{\"proofPoints\":{\"a\":[\"1\",\"18104499930818305143361187733659014043953751050617136254447624192327280445771\",\"1\"],\"b\":[[\"1\",\"11928003240637992017698644299021052465098754853899210401706726930513411198353\"],[\"2597127058046351054449743605218058440565462021354202666955356076272028963802\",\"3385145993275542896693643488618289924488296318344621918448585222369718288892\"],[\"1\",\"0\"]],\"c\":[\"395141536511114303768253959602639884294254888080713473665269769443249414257\",\"21430657725804540809568084344756144327539843580919730138594118365564728808275\",\"1\"]},\"issBase64Details\":{\"value\":\"yJpc3MiOiJodHRwczovL2FjY291bnRzLmdvb2dsZS5jb20iLC\",\"indexMod4\":1},\"headerBase64\":\"eyJhbGciOiJSUzI1NiIsImtpZCI6ImM5YWZkYTM2ODJlYmYwOWViMzA1NWMxYzRiZDM5Yjc1MWZiZjgxOTUiLCJ0eXAiOiJKV1QifQ\"}", "4959624758616676340947699768172740454110375485415332267384397278368360470616").unwrap();
+    let _ = ZkLoginInputs::from_json("{\"proofPoints\":{\"a\":[\"18104499930818305143361187733659014043953751050617136254447624192327280445771\",\"1\"],\"b\":[[\"11369230593957954942221175389182778816136534144714579815927653075736806430994\",\"11928003240637992017698644299021052465098754853899210401706726930513411198353\"],[\"2597127058046351054449743605218058440565462021354202666955356076272028963802\",\"3385145993275542896693643488618289924488296318344621918448585222369718288892\"],[\"1\",\"0\"]],\"c\":[\"395141536511114303768253959602639884294254888080713473665269769443249414257\",\"21430657725804540809568084344756144327539843580919730138594118365564728808275\",\"1\"]},\"issBase64Details\":{\"value\":\"yJpc3MiOiJodHRwczovL2FjY291bnRzLmdvb2dsZS5jb20iLC\",\"indexMod4\":1},\"headerBase64\":\"eyJhbGciOiJSUzI1NiIsImtpZCI6ImM5YWZkYTM2ODJlYmYwOWViMzA1NWMxYzRiZDM5Yjc1MWZiZjgxOTUiLCJ0eXAiOiJKV1QifQ\"}", "4959624758616676340947699768172740454110375485415332267384397278368360470616").is_err();
+    let _ = ZkLoginInputs::from_json("{\"proofPoints\":{\"a\":[\"18104499930818305143361187733659014043953751050617136254447624192327280445771\",\"1\"],\"b\":[[\"11369230593957954942221175389182778816136534144714579815927653075736806430994\",\"11928003240637992017698644299021052465098754853899210401706726930513411198353\"],[\"2597127058046351054449743605218058440565462021354202666955356076272028963802\",\"3385145993275542896693643488618289924488296318344621918448585222369718288892\"],[\"1\",\"0\"]],\"c\":[\"395141536511114303768253959602639884294254888080713473665269769443249414257\",\"21430657725804540809568084344756144327539843580919730138594118365564728808275\",\"1\"]},\"issBase64Details\":{\"value\":\"yJpc3MiOiJodHRwczovL2FjY291bnRzLmdvb2dsZS5jb20iLC\",\"indexMod4\":1},\"headerBase64\":\"eyJhbGciOiJSUzI1NiIsImtpZCI6ImM5YWZkYTM2ODJlYmYwOWViMzA1NWMxYzRiZDM5Yjc1MWZiZjgxOTUiLCJ0eXAiOiJKV1QifQ\"}", "bad seed").is_err();
+
+    let mut eph_pubkey_bytes = vec![0];
+    eph_pubkey_bytes.extend(
+        BigUint::from_str(
+            "3598866369818193253063936208363210863933653800990958031560302098730308306242903464",
+        )
         .unwrap()
         .to_bytes_be(),
     );
@@ -652,6 +2192,353 @@ fn test_alternative_iss_for_google() {
     assert!(invalid_res.is_err());
 }
 
+#[test]
+fn test_dummy_zk_login_inputs_reader() {
+    let reader = ZkLoginInputsReader::dummy();
+    let json = reader.to_prover_json().unwrap();
+    let parsed: ZkLoginInputsReader = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed.to_prover_json().unwrap(), json);
+    assert_eq!(reader.get_jwk_id().unwrap(), google_alt_iss_jwk().0);
+
+    // The dummy proof does not verify, even against the JWK it refers to.
+    let input = ZkLoginInputs::from_reader(reader, "1").unwrap();
+    assert!(input.get_proof().as_arkworks().is_ok());
+    let mut eph_pubkey_bytes = vec![0];
+    eph_pubkey_bytes.extend([1; 32]);
+    let (jwk_id, jwk) = google_alt_iss_jwk();
+    let all_jwk = ImHashMap::unit(jwk_id, jwk);
+
+    for env in [ZkLoginEnv::Test, ZkLoginEnv::Prod] {
+        assert!(verify_zk_login(&input, 10, &eph_pubkey_bytes, &all_jwk, &env).is_err());
+    }
+}
+
+#[test]
+fn test_batch_verify_zk_login() {
+    let input =
+        ZkLoginInputs::from_json(GOOGLE_ALT_ISS_PROOF, GOOGLE_ALT_ISS_ADDRESS_SEED).unwrap();
+    let invalid_proof_input =
+        ZkLoginInputs::from_json(GOOGLE_ALT_ISS_INVALID_PROOF, GOOGLE_ALT_ISS_ADDRESS_SEED)
+            .unwrap();
+    let eph_pubkey_bytes = google_alt_iss_eph_pubkey();
+    let (jwk_id, jwk) = google_alt_iss_jwk();
+    let all_jwk = ImHashMap::unit(jwk_id, jwk);
+
+    let env = ZkLoginEnv::Test;
+
+    // All valid.
+    let batch = [
+        (&input, 10000, eph_pubkey_bytes.as_slice()),
+        (&input, 10000, eph_pubkey_bytes.as_slice()),
+    ];
+    assert_eq!(
+        batch_verify_zk_login(&batch, &all_jwk, &env),
+        vec![Ok(()), Ok(())]
+    );
+
+    // Invalid proofs and wrong public inputs fail without affecting the rest of the batch.
+    let batch = [
+        (&input, 10000, eph_pubkey_bytes.as_slice()),
+        (&invalid_proof_input, 10000, eph_pubkey_bytes.as_slice()),
+        (&input, 10001, eph_pubkey_bytes.as_slice()),
+        (&input, 10000, eph_pubkey_bytes.as_slice()),
+    ];
+    let results = batch_verify_zk_login(&batch, &all_jwk, &env);
+    assert_eq!(results.len(), batch.len());
+    for (result, (input, max_epoch, eph_pubkey_bytes)) in results.iter().zip(batch) {
+        assert_eq!(
+            *result,
+            verify_zk_login(input, max_epoch, eph_pubkey_bytes, &all_jwk, &env)
+        );
+    }
+    assert!(results[0].is_ok() && results[3].is_ok());
+    assert!(results[1].is_err() && results[2].is_err());
+
+    let results = batch_verify_zk_login(&batch[..1], &ImHashMap::new(), &env);
+    assert_eq!(
+        results,
+        vec![Err(FastCryptoError::GeneralError(format!(
+            "JWK not found ({} - {})",
+            input.get_iss(),
+            input.get_kid()
+        )))]
+    );
+
+    // The proofs do not verify against the production key.
+    assert!(batch_verify_zk_login(&batch[..1], &all_jwk, &ZkLoginEnv::Prod)[0].is_err());
+    assert!(batch_verify_zk_login(&[], &all_jwk, &env).is_empty());
+}
+
+#[cfg(feature = "client")]
+#[tokio::test]
+async fn test_verify_zk_login_with_jwk_refresh() {
+    let input =
+        ZkLoginInputs::from_json(GOOGLE_ALT_ISS_PROOF, GOOGLE_ALT_ISS_ADDRESS_SEED).unwrap();
+    let eph_pubkey_bytes = google_alt_iss_eph_pubkey();
+    let (jwk_id, jwk) = google_alt_iss_jwk();
+    let client = reqwest::Client::new();
+
+    // The JWK is cached, so no refresh is needed.
+    let mut all_jwk = ImHashMap::new();
+    all_jwk.insert(jwk_id.clone(), jwk.clone());
+    assert!(verify_zk_login_with_jwk_refresh(
+        &input,
+        10000,
+        &eph_pubkey_bytes,
+        &mut all_jwk,
+        &ZkLoginEnv::Test,
+        &client
+    )
+    .await
+    .is_ok());
+    assert_eq!(all_jwk.len(), 1);
+
+    // A wrong max_epoch fails without refreshing since the kid is known.
+    assert_eq!(
+        verify_zk_login_with_jwk_refresh(
+            &input,
+            10001,
+            &eph_pubkey_bytes,
+            &mut all_jwk,
+            &ZkLoginEnv::Test,
+            &client
+        )
+        .await,
+        Err(FastCryptoError::GeneralError(
+            "Groth16 proof verify failed".to_string()
+        ))
+    );
+    assert_eq!(all_jwk.len(), 1);
+}
+
+#[cfg(feature = "e2e")]
+#[tokio::test]
+async fn test_verify_zk_login_with_jwk_refresh_from_google() {
+    let input =
+        ZkLoginInputs::from_json(GOOGLE_ALT_ISS_PROOF, GOOGLE_ALT_ISS_ADDRESS_SEED).unwrap();
+    let eph_pubkey_bytes = google_alt_iss_eph_pubkey();
+    let (jwk_id, _) = google_alt_iss_jwk();
+    let client = reqwest::Client::new();
+
+    // The kid has long been rotated out by Google, so it is not found after refreshing either.
+    let mut all_jwk = ImHashMap::new();
+    assert_eq!(
+        verify_zk_login_with_jwk_refresh(
+            &input,
+            10000,
+            &eph_pubkey_bytes,
+            &mut all_jwk,
+            &ZkLoginEnv::Test,
+            &client
+        )
+        .await,
+        Err(FastCryptoError::GeneralError(format!(
+            "JWK not found after refresh ({} - {})",
+            jwk_id.iss, jwk_id.kid
+        )))
+    );
+    assert!(!all_jwk.is_empty());
+}
+
+#[test]
+fn test_public_inputs_hash() {
+    let reader: ZkLoginInputsReader = serde_json::from_str(GOOGLE_ALT_ISS_PROOF).unwrap();
+    let address_seed = GOOGLE_ALT_ISS_ADDRESS_SEED;
+    let eph_pubkey_bytes = google_alt_iss_eph_pubkey();
+    let modulus = Base64UrlUnpadded::decode_vec(GOOGLE_ALT_ISS_JWK_N).unwrap();
+
+    let hash = reader
+        .public_inputs_hash(address_seed, &eph_pubkey_bytes, &modulus, 10000)
+        .unwrap();
+    let inputs = ZkLoginInputs::from_reader(reader.clone(), address_seed).unwrap();
+    assert_eq!(
+        Bn254Fr::from(&hash),
+        inputs
+            .calculate_all_inputs_hash(&eph_pubkey_bytes, &modulus, 10000)
+            .unwrap()
+    );
+
+    // The proof verifies against the hash computed from the reader.
+    assert!(verify_zk_login_proof_with_fixed_vk(
+        &ZkLoginEnv::Test,
+        &inputs.get_proof().as_arkworks().unwrap(),
+        &[(&hash).into()],
+    )
+    .unwrap());
+
+    // A different max_epoch yields a different public input which the proof does not verify against.
+    let other_hash = reader
+        .public_inputs_hash(address_seed, &eph_pubkey_bytes, &modulus, 10001)
+        .unwrap();
+    assert_ne!(hash, other_hash);
+    assert!(!verify_zk_login_proof_with_fixed_vk(
+        &ZkLoginEnv::Test,
+        &inputs.get_proof().as_arkworks().unwrap(),
+        &[(&other_hash).into()],
+    )
+    .unwrap());
+
+    // Invalid address seed.
+    assert!(reader
+        .public_inputs_hash("bad seed", &eph_pubkey_bytes, &modulus, 10000)
+        .is_err());
+}
+
+#[test]
+fn test_verify_proof_jwk_current() {
+    let reader: ZkLoginInputsReader = serde_json::from_str(GOOGLE_ALT_ISS_PROOF).unwrap();
+    let (jwk_id, _) = google_alt_iss_jwk();
+    assert_eq!(reader.get_jwk_id().unwrap(), jwk_id);
+
+    let jwk = JWK {
+        kty: "RSA".to_string(),
+        e: "AQAB".to_string(),
+        n: "n".to_string(),
+        alg: "RS256".to_string(),
+    };
+    let other_id = JwkId::new(jwk_id.iss.clone(), "other".to_string());
+    assert!(verify_proof_jwk_current(
+        &reader,
+        &[
+            (other_id.clone(), jwk.clone()),
+            (jwk_id.clone(), jwk.clone())
+        ]
+    )
+    .is_ok());
+
+    // The key has been rotated out.
+    assert_eq!(
+        verify_proof_jwk_current(&reader, &[(other_id, jwk.clone())]),
+        Err(FastCryptoError::GeneralError(format!(
+            "JWK not currently published ({} - {})",
+            jwk_id.iss, jwk_id.kid
+        )))
+    );
+
+    // The same kid published by another issuer does not match.
+    let other_iss = JwkId::new("https://example.com".to_string(), jwk_id.kid.clone());
+    assert!(verify_proof_jwk_current(&reader, &[(other_iss, jwk)]).is_err());
+    assert!(verify_proof_jwk_current(&reader, &[]).is_err());
+}
+
+#[test]
+fn test_from_proof_components() {
+    let address_seed = GOOGLE_ALT_ISS_ADDRESS_SEED;
+    let input = ZkLoginInputs::from_json(GOOGLE_ALT_ISS_PROOF, address_seed).unwrap();
+    let proof = input.get_proof().as_arkworks().unwrap();
+    assert_eq!(
+        ZkLoginProof::from_arkworks(&proof).as_arkworks().unwrap(),
+        proof
+    );
+
+    let mut a = Vec::new();
+    proof.a.serialize_compressed(&mut a).unwrap();
+    let mut b = Vec::new();
+    proof.b.serialize_compressed(&mut b).unwrap();
+    let mut c = Vec::new();
+    proof.c.serialize_compressed(&mut c).unwrap();
+    let (a, b, c) = (Hex::encode(a), Hex::encode(b), Hex::encode(c));
+    let iss_value = "yJpc3MiOiJodHRwczovL2FjY291bnRzLmdvb2dsZS5jb20iLC";
+    let header = "eyJhbGciOiJSUzI1NiIsImtpZCI6ImM5YWZkYTM2ODJlYmYwOWViMzA1NWMxYzRiZDM5Yjc1MWZiZjgxOTUiLCJ0eXAiOiJKV1QifQ";
+
+    let reconstructed =
+        ZkLoginInputs::from_proof_components(&a, &b, &c, iss_value, 1, header, address_seed)
+            .unwrap();
+    assert_eq!(reconstructed.get_proof().as_arkworks().unwrap(), proof);
+    assert_eq!(reconstructed.get_iss(), input.get_iss());
+    assert_eq!(reconstructed.get_kid(), input.get_kid());
+    assert_eq!(reconstructed.get_address_seed(), input.get_address_seed());
+
+    // Each invalid component is reported.
+    let error = |component: &str| {
+        FastCryptoError::GeneralError(format!("Invalid proof component {}", component))
+    };
+    assert_eq!(
+        ZkLoginInputs::from_proof_components(&b, &b, &c, iss_value, 1, header, address_seed)
+            .unwrap_err(),
+        error("a")
+    );
+    assert_eq!(
+        ZkLoginInputs::from_proof_components(&a, &a, &c, iss_value, 1, header, address_seed)
+            .unwrap_err(),
+        error("b")
+    );
+    assert_eq!(
+        ZkLoginInputs::from_proof_components(&a, &b, "0xzz", iss_value, 1, header, address_seed)
+            .unwrap_err(),
+        error("c")
+    );
+    assert_eq!(
+        ZkLoginInputs::from_proof_components(&a, &b, &c, iss_value, 1, "header", address_seed)
+            .unwrap_err(),
+        error("header_base64")
+    );
+    assert_eq!(
+        ZkLoginInputs::from_proof_components(&a, &b, &c, iss_value, 1, header, "seed").unwrap_err(),
+        error("address_seed")
+    );
+    assert_eq!(
+        ZkLoginInputs::from_proof_components(&a, &b, &c, "!!!!", 1, header, address_seed)
+            .unwrap_err(),
+        error("iss_base64_details")
+    );
+}
+
+#[test]
+fn test_to_prover_json() {
+    let json = GOOGLE_ALT_ISS_PROOF;
+    let reader: ZkLoginInputsReader = serde_json::from_str(json).unwrap();
+    assert_eq!(reader.to_prover_json().unwrap(), json);
+
+    let reparsed: ZkLoginInputsReader =
+        serde_json::from_str(&reader.to_prover_json().unwrap()).unwrap();
+    assert_eq!(reparsed.to_prover_json().unwrap(), json);
+}
+
+#[cfg(feature = "client")]
+#[test]
+fn test_parse_proof_response() {
+    let json = GOOGLE_ALT_ISS_PROOF;
+
+    // Without metadata all fields are empty.
+    let response = parse_proof_response(json.as_bytes()).unwrap();
+    assert_eq!(response.inputs.to_prover_json().unwrap(), json);
+    assert_eq!(response.metadata, ProofMetadata::default());
+
+    let mut value: serde_json::Value = serde_json::from_str(json).unwrap();
+    value["generationTimeMs"] = serde_json::json!(1234);
+    value["circuitVersion"] = serde_json::json!(2);
+    value["warnings"] = serde_json::json!(["slow"]);
+    let response = parse_proof_response(value.to_string().as_bytes()).unwrap();
+    assert_eq!(response.inputs.to_prover_json().unwrap(), json);
+    assert_eq!(
+        response.metadata,
+        ProofMetadata {
+            generation_time_ms: Some(1234),
+            circuit_version: Some(2),
+            warnings: vec!["slow".to_string()],
+        }
+    );
+
+    // Responses of snake_case provers are parsed the same way.
+    let snake_case = json
+        .replace("proofPoints", "proof_points")
+        .replace("issBase64Details", "iss_base64_details")
+        .replace("indexMod4", "index_mod_4")
+        .replace("headerBase64", "header_base64");
+    let snake_case = format!(
+        "{},\"generation_time_ms\":1234}}",
+        snake_case.strip_suffix('}').unwrap()
+    );
+    let response = parse_proof_response(snake_case.as_bytes()).unwrap();
+    assert_eq!(response.inputs.to_prover_json().unwrap(), json);
+    assert_eq!(response.metadata.generation_time_ms, Some(1234));
+
+    value["circuitVersion"] = serde_json::json!("two");
+    assert!(parse_proof_response(value.to_string().as_bytes()).is_err());
+    assert!(parse_proof_response(b"{}").is_err());
+}
+
 #[test]
 fn test_base64_to_bitarray() {
     let input = "a";
@@ -682,3 +2569,240 @@ fn test_bitarray_to_bytearray() {
     assert!(bitarray_to_bytearray(&[0; 16]).is_ok());
     assert!(bitarray_to_bytearray(&[0; 17]).is_err());
 }
+
+/// Serve the given responses, given as HTTP status and JSON body, on a local port, one per
+/// connection. Returns the URL of the server and a counter of the requests received.
+#[cfg(feature = "client")]
+fn serve_responses(responses: Vec<(u16, &'static str)>) -> (String, Arc<AtomicUsize>) {
+    let (url, received, _) = serve_and_record(responses);
+    (url, received)
+}
+
+/// Same as [`serve_responses`] but also records the requests, lowercased.
+#[cfg(feature = "client")]
+fn serve_and_record(
+    responses: Vec<(u16, &'static str)>,
+) -> (String, Arc<AtomicUsize>, Arc<Mutex<Vec<String>>>) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let received = Arc::new(AtomicUsize::new(0));
+    let counter = received.clone();
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let recorded = requests.clone();
+    std::thread::spawn(move || {
+        for (status, body) in responses {
+            let (mut stream, _) = listener.accept().unwrap();
+            // Read the full request before responding.
+            let mut request = Vec::new();
+            let mut buf = [0u8; 1024];
+            let text = loop {
+                let n = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request).to_lowercase();
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let length = text[..end]
+                        .lines()
+                        .find_map(|line| line.strip_prefix("content-length:"))
+                        .map_or(0, |v| v.trim().parse::<usize>().unwrap());
+                    if request.len() >= end + 4 + length {
+                        break text;
+                    }
+                }
+                if n == 0 {
+                    break text;
+                }
+            };
+            recorded.lock().unwrap().push(text);
+            counter.fetch_add(1, Ordering::SeqCst);
+            let response = format!(
+                "HTTP/1.1 {} Status\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+        }
+    });
+    (url, received, requests)
+}
+
+#[cfg(feature = "client")]
+#[tokio::test]
+async fn test_get_salt_with_retry() {
+    let options = SaltRetryOptions {
+        max_attempts: 3,
+        initial_backoff: Duration::from_millis(1),
+        request: SaltRequestOptions::default(),
+    };
+    let salt = "129390038577185583942388216820280642146";
+    let body = r#"{"salt":"129390038577185583942388216820280642146"}"#;
+
+    // Two server errors are retried.
+    let (url, received) = serve_responses(vec![(503, ""), (503, ""), (200, body)]);
+    assert_eq!(
+        get_salt_with_retry("jwt", &url, &options).await.unwrap(),
+        salt
+    );
+    assert_eq!(received.load(Ordering::SeqCst), 3);
+
+    // The error of the last attempt is returned if all attempts fail.
+    let (url, received) = serve_responses(vec![(502, ""), (503, ""), (503, ""), (200, body)]);
+    assert_eq!(
+        get_salt_with_retry("jwt", &url, &options).await,
+        Err(FastCryptoError::GeneralError(
+            "Salt server returned 503 Service Unavailable".to_string()
+        ))
+    );
+    assert_eq!(received.load(Ordering::SeqCst), 3);
+
+    // Client errors are not retried.
+    let (url, received) = serve_responses(vec![(400, ""), (200, body)]);
+    assert_eq!(
+        get_salt_with_retry("jwt", &url, &options).await,
+        Err(FastCryptoError::GeneralError(
+            "Salt server returned 400 Bad Request".to_string()
+        ))
+    );
+    assert_eq!(received.load(Ordering::SeqCst), 1);
+
+    // The request format is used for every attempt.
+    let custom = SaltRetryOptions {
+        request: SaltRequestOptions {
+            token_field: "jwt".to_string(),
+            extra_headers: vec![("X-Api-Key".to_string(), "secret".to_string())],
+        },
+        ..options.clone()
+    };
+    let (url, _, requests) = serve_and_record(vec![(503, ""), (200, body)]);
+    assert_eq!(
+        get_salt_with_retry("jwt", &url, &custom).await.unwrap(),
+        salt
+    );
+    let requests = requests.lock().unwrap().clone();
+    assert_eq!(requests.len(), 2);
+    for request in requests {
+        assert!(request.ends_with(r#"{"jwt":"jwt"}"#));
+        assert!(request.contains("x-api-key: secret\r\n"));
+    }
+
+    let no_attempts = SaltRetryOptions {
+        max_attempts: 0,
+        ..options
+    };
+    assert!(get_salt_with_retry("jwt", &url, &no_attempts)
+        .await
+        .is_err());
+}
+
+#[cfg(feature = "client")]
+#[tokio::test]
+async fn test_get_salt_with_options() {
+    let salt = "129390038577185583942388216820280642146";
+    let body = r#"{"salt":"129390038577185583942388216820280642146"}"#;
+
+    // The defaults give the same request as get_salt.
+    let (url, _, requests) = serve_and_record(vec![(200, body), (200, body)]);
+    assert_eq!(get_salt("jwt", &url).await.unwrap(), salt);
+    assert_eq!(
+        get_salt_with_options("jwt", &url, &SaltRequestOptions::default())
+            .await
+            .unwrap(),
+        salt
+    );
+    let requests = requests.lock().unwrap().clone();
+    assert!(requests[0].ends_with(r#"{"token":"jwt"}"#));
+    assert_eq!(requests[0], requests[1]);
+
+    let options = SaltRequestOptions {
+        token_field: "jwt".to_string(),
+        extra_headers: vec![("X-Api-Key".to_string(), "secret".to_string())],
+    };
+    let (url, _, requests) = serve_and_record(vec![(200, body)]);
+    assert_eq!(
+        get_salt_with_options("jwt", &url, &options).await.unwrap(),
+        salt
+    );
+    let request = requests.lock().unwrap()[0].clone();
+    assert!(request.ends_with(r#"{"jwt":"jwt"}"#));
+    assert!(request.contains("x-api-key: secret\r\n"));
+}
+
+#[test]
+fn test_derive_salt_deterministic() {
+    let iss = "https://accounts.google.com";
+    let sub = "106294049240999307923";
+    let master_secret = [7u8; 32];
+
+    let salt = derive_salt_deterministic(&master_secret, iss, sub).unwrap();
+    assert_eq!(
+        salt,
+        derive_salt_deterministic(&master_secret, iss, sub).unwrap()
+    );
+    assert!(BigUint::from_str(&salt).unwrap().bits() <= 128);
+    assert!(gen_address_seed(&salt, "sub", sub, "aud").is_ok());
+
+    // Changing any of the inputs changes the salt.
+    assert_ne!(
+        salt,
+        derive_salt_deterministic(&[8u8; 32], iss, sub).unwrap()
+    );
+    assert_ne!(
+        salt,
+        derive_salt_deterministic(&master_secret, "https://example.com", sub).unwrap()
+    );
+    assert_ne!(
+        salt,
+        derive_salt_deterministic(&master_secret, iss, "1").unwrap()
+    );
+
+    // The claims are length prefixed, so moving bytes between iss and sub changes the salt.
+    assert_ne!(
+        derive_salt_deterministic(&master_secret, "ab", "c").unwrap(),
+        derive_salt_deterministic(&master_secret, "a", "bc").unwrap()
+    );
+
+    assert_eq!(
+        derive_salt_deterministic(&[], iss, sub),
+        Err(FastCryptoError::InputLengthWrong {
+            expected: 1,
+            actual: 0,
+        })
+    );
+}
+
+fn address_from_claims(iss: &str, aud: &str, sub: &str, salt: u128) -> [u8; 32] {
+    let address_seed = gen_address_seed(&salt.to_string(), "sub", sub, aud).unwrap();
+    get_zk_login_address(&Bn254FrElement::from_str(&address_seed).unwrap(), iss).unwrap()
+}
+
+proptest::proptest! {
+    #[test]
+    fn test_zk_login_address_injective(
+        // Issuers longer than 255 bytes exercise the truncated length prefix.
+        iss in "[a-z:/.]{1,300}",
+        aud in "[a-z0-9.-]{1,64}",
+        sub in "[0-9]{1,32}",
+        salt in proptest::prelude::any::<u128>(),
+        other_iss in "[a-z:/.]{1,300}",
+        other_aud in "[a-z0-9.-]{1,64}",
+        other_sub in "[0-9]{1,32}",
+        other_salt in proptest::prelude::any::<u128>(),
+        // Bitmask selecting which of the four claims differ in the second tuple.
+        changed in 1u8..16,
+    ) {
+        let address = address_from_claims(&iss, &aud, &sub, salt);
+        proptest::prop_assert_eq!(address, address_from_claims(&iss, &aud, &sub, salt));
+
+        let other = (
+            if changed & 1 != 0 { other_iss } else { iss.clone() },
+            if changed & 2 != 0 { other_aud } else { aud.clone() },
+            if changed & 4 != 0 { other_sub } else { sub.clone() },
+            if changed & 8 != 0 { other_salt } else { salt },
+        );
+        proptest::prop_assume!((&iss, &aud, &sub, salt) != (&other.0, &other.1, &other.2, other.3));
+        proptest::prop_assert_ne!(
+            address,
+            address_from_claims(&other.0, &other.1, &other.2, other.3)
+        );
+    }
+}
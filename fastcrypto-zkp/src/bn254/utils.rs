@@ -2,38 +2,88 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::bn254::zk_login::poseidon_zk_login;
-use crate::bn254::zk_login::{OIDCProvider, ZkLoginInputsReader};
+use crate::bn254::zk_login::{OIDCProvider, ZkLoginInputs, ZkLoginInputsReader, JWK};
 use crate::bn254::zk_login_api::Bn254Fr;
-use crate::zk_login_utils::Bn254FrElement;
+use crate::zk_login_utils::{
+    biguint_to_bn254_fr, fr_from_decimal_string, fr_to_decimal_string, Bn254FrElement,
+};
+use ark_ff::UniformRand;
+use fastcrypto::ed25519::Ed25519PublicKey;
+use fastcrypto::encoding::{Encoding as _, Hex};
 use fastcrypto::error::FastCryptoError;
 use fastcrypto::hash::{Blake2b256, HashFunction};
+use fastcrypto::hmac::{hkdf_sha3_256, HkdfIkm};
 use fastcrypto::rsa::Base64UrlUnpadded;
 use fastcrypto::rsa::Encoding;
+use fastcrypto::secp256k1::Secp256k1PublicKey;
+use fastcrypto::secp256r1::Secp256r1PublicKey;
+use fastcrypto::traits::{AllowedRng, ToFromBytes, VerifyingKey};
 use num_bigint::BigUint;
+#[cfg(feature = "client")]
+use rand::Rng;
+#[cfg(feature = "client")]
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "client")]
 use serde_json::json;
+use serde_json::Value;
+use std::ops::Range;
 use std::str::FromStr;
+#[cfg(feature = "client")]
+use std::time::Duration;
+use subtle::ConstantTimeEq;
 
 use super::zk_login::hash_ascii_str_to_field;
 
 const ZK_LOGIN_AUTHENTICATOR_FLAG: u8 = 0x05;
-const MAX_KEY_CLAIM_NAME_LENGTH: u8 = 32;
-const MAX_KEY_CLAIM_VALUE_LENGTH: u8 = 115;
-const MAX_AUD_VALUE_LENGTH: u8 = 145;
+/// The ephemeral public key is split into its last 16 bytes and the bytes before them, which must be
+/// non-empty and fit in 31 bytes to be mapped to a field element without reduction.
+const MIN_EPH_PK_LENGTH: usize = 17;
+const MAX_EPH_PK_LENGTH: usize = 47;
+const SALT_LENGTH: usize = 16;
+/// All supported ephemeral signature schemes have 64 byte signatures.
+const EPH_SIGNATURE_LENGTH: usize = 64;
+const SALT_DERIVATION_DOMAIN: &[u8] = b"ZKLOGIN_SALT";
+/// Cap on the number of times the backoff between salt requests is doubled.
+#[cfg(feature = "client")]
+const MAX_BACKOFF_DOUBLINGS: u32 = 16;
 
 /// Calculate the Sui address based on address seed and address params.
 pub fn get_zk_login_address(
     address_seed: &Bn254FrElement,
     iss: &str,
 ) -> Result<[u8; 32], FastCryptoError> {
-    let mut hasher = Blake2b256::default();
-    hasher.update([ZK_LOGIN_AUTHENTICATOR_FLAG]);
+    Ok(Blake2b256::digest(zk_login_address_preimage(address_seed, iss)?).digest)
+}
+
+/// Return the bytes hashed by [`get_zk_login_address`] to get the address: the zkLogin flag, the
+/// length of iss as a single byte, iss and the address seed padded to 32 bytes.
+pub fn zk_login_address_preimage(
+    address_seed: &Bn254FrElement,
+    iss: &str,
+) -> Result<Vec<u8>, FastCryptoError> {
     let bytes = iss.as_bytes();
-    hasher.update([bytes.len() as u8]);
-    hasher.update(bytes);
-    hasher.update(address_seed.padded());
-    Ok(hasher.finalize().digest)
+    let mut preimage = vec![ZK_LOGIN_AUTHENTICATOR_FLAG, bytes.len() as u8];
+    preimage.extend_from_slice(bytes);
+    preimage.extend_from_slice(address_seed.padded());
+    Ok(preimage)
+}
+
+/// Compare an address given as a hex string, e.g. as returned by a full node, with an address
+/// computed with [`get_zk_login_address`]. The hex string may have a "0x" or "0X" prefix and may
+/// use upper or lower case characters. The comparison of the bytes is done in constant time.
+///
+/// Returns [`FastCryptoError::InvalidInput`] if the string is not valid hex and
+/// [`FastCryptoError::InputLengthWrong`] if it does not encode 32 bytes.
+pub fn zk_login_address_eq(a: &str, b: &[u8; 32]) -> Result<bool, FastCryptoError> {
+    let a = Hex::decode(&a.to_ascii_lowercase())?;
+    if a.len() != b.len() {
+        return Err(FastCryptoError::InputLengthWrong {
+            expected: b.len(),
+            actual: a.len(),
+        });
+    }
+    Ok(a.ct_eq(b).into())
 }
 
 /// Calculate the Sui address based on address seed and address params.
@@ -43,27 +93,442 @@ pub fn gen_address_seed(
     value: &str, // i.e. the sub value
     aud: &str,   // i.e. the client ID
 ) -> Result<String, FastCryptoError> {
-    let salt_hash = poseidon_zk_login(&[(&Bn254FrElement::from_str(salt)?).into()])?;
-    gen_address_seed_with_salt_hash(&salt_hash.to_string(), name, value, aud)
+    gen_address_seed_with_params(salt, name, value, aud, &CircuitParams::default())
+}
+
+/// Same as [`gen_address_seed`] but hashes the claims with the widths of the given circuit, e.g.
+/// as given by [`CircuitParams::for_version`].
+pub fn gen_address_seed_with_params(
+    salt: &str,
+    name: &str,  // i.e. "sub"
+    value: &str, // i.e. the sub value
+    aud: &str,   // i.e. the client ID
+    params: &CircuitParams,
+) -> Result<String, FastCryptoError> {
+    gen_address_seed_with_salt_hash(&salt_hash(salt)?.to_string(), name, value, aud, params)
 }
 
-/// Same as [`gen_address_seed`] but takes the poseidon hash of the salt as input instead of the salt.
+/// Calculate the poseidon hash of the salt, which is what the address seed commits to. This can be
+/// shared in place of the salt, e.g. with [`crate::bn254::zk_login_api::verify_zk_login_id`],
+/// keeping the salt itself private.
+pub fn salt_hash(salt: &str) -> Result<Bn254FrElement, FastCryptoError> {
+    let hash = poseidon_zk_login(&[(&Bn254FrElement::from_str(salt)?).into()])?;
+    Ok((&hash).into())
+}
+
+/// Derive a salt deterministically from a master secret and the identity of the user, i.e. the iss
+/// and sub claims, as an alternative to fetching the salt from a salt server. The output is a
+/// canonical decimal string that can be passed to [`gen_address_seed`].
+///
+/// The salt is computed as a 16 byte output of HKDF-SHA3-256 over the master secret with the
+/// length prefixed iss and sub as info. The address depends on the salt, so the same master secret
+/// is required to recover the same address, and if the master secret is lost, so is the address.
+pub fn derive_salt_deterministic(
+    master_secret: &[u8],
+    iss: &str,
+    sub: &str,
+) -> Result<String, FastCryptoError> {
+    if master_secret.is_empty() {
+        return Err(FastCryptoError::InputLengthWrong {
+            expected: 1,
+            actual: 0,
+        });
+    }
+    let mut info = Vec::with_capacity(16 + iss.len() + sub.len());
+    for claim in [iss, sub] {
+        info.extend_from_slice(&(claim.len() as u64).to_be_bytes());
+        info.extend_from_slice(claim.as_bytes());
+    }
+    let ikm = HkdfIkm::from_bytes(master_secret)?;
+    let salt = hkdf_sha3_256(&ikm, SALT_DERIVATION_DOMAIN, &info, SALT_LENGTH)?;
+    Ok(BigUint::from_bytes_be(&salt).to_string())
+}
+
+/// The maximum lengths of the claims hashed into the address seed. These are fixed by the circuit,
+/// and hashing with widths other than those of the circuit the proof is generated for gives a
+/// different address seed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitParams {
+    /// The maximum length of the key claim name, e.g. "sub".
+    pub max_key_claim_name_length: u8,
+    /// The maximum length of the key claim value.
+    pub max_key_claim_value_length: u8,
+    /// The maximum length of the aud claim value.
+    pub max_aud_value_length: u8,
+}
+
+/// The version of the current circuit, whose parameters are given by [`CircuitParams::default`].
+pub const CURRENT_CIRCUIT_VERSION: u32 = 1;
+
+impl Default for CircuitParams {
+    fn default() -> Self {
+        Self {
+            max_key_claim_name_length: 32,
+            max_key_claim_value_length: 115,
+            max_aud_value_length: 145,
+        }
+    }
+}
+
+impl CircuitParams {
+    /// Return the parameters of the given circuit version or [`FastCryptoError::InvalidInput`] if
+    /// the version is unknown.
+    pub fn for_version(version: u32) -> Result<Self, FastCryptoError> {
+        match version {
+            CURRENT_CIRCUIT_VERSION => Ok(Self::default()),
+            _ => Err(FastCryptoError::InvalidInput),
+        }
+    }
+}
+
+/// Same as [`gen_address_seed`] but takes the poseidon hash of the salt as input instead of the salt,
+/// and hashes the claims with the widths of the given circuit.
 pub(crate) fn gen_address_seed_with_salt_hash(
     salt_hash: &str,
     name: &str,  // i.e. "sub"
     value: &str, // i.e. the sub value
     aud: &str,   // i.e. the client ID
+    params: &CircuitParams,
 ) -> Result<String, FastCryptoError> {
-    Ok(poseidon_zk_login(&[
-        hash_ascii_str_to_field(name, MAX_KEY_CLAIM_NAME_LENGTH)?,
-        hash_ascii_str_to_field(value, MAX_KEY_CLAIM_VALUE_LENGTH)?,
-        hash_ascii_str_to_field(aud, MAX_AUD_VALUE_LENGTH)?,
+    let address_seed = poseidon_zk_login(&[
+        hash_ascii_str_to_field(name, params.max_key_claim_name_length)?,
+        hash_ascii_str_to_field(value, params.max_key_claim_value_length)?,
+        hash_ascii_str_to_field(aud, params.max_aud_value_length)?,
         (&Bn254FrElement::from_str(salt_hash)?).into(),
-    ])?
-    .to_string())
+    ])?;
+    Ok(fr_to_decimal_string(&address_seed))
+}
+
+/// The claim in the JWT whose value identifies the user and is used for address derivation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyClaimName {
+    /// The `sub` claim.
+    Sub,
+    /// The `email` claim.
+    Email,
+}
+
+impl KeyClaimName {
+    /// The name of the claim as it appears in the JWT.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeyClaimName::Sub => "sub",
+            KeyClaimName::Email => "email",
+        }
+    }
+}
+
+/// The claims of a JWT needed to derive the zkLogin address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressClaims {
+    /// The `iss` claim.
+    pub iss: String,
+    /// The `aud` claim.
+    pub aud: String,
+    /// The value of the key claim.
+    pub key_value: String,
+}
+
+/// Decode the payload of the given JWT into a JSON object. The signature is not verified.
+pub fn parse_jwt_payload(jwt: &str) -> Result<serde_json::Map<String, Value>, FastCryptoError> {
+    let parts: Vec<&str> = jwt.split('.').collect();
+    if parts.len() != 3 {
+        return Err(FastCryptoError::InvalidInput);
+    }
+    let decoded = Base64UrlUnpadded::decode_vec(parts[1].trim_end_matches('='))
+        .map_err(|_| FastCryptoError::InvalidInput)?;
+    serde_json::from_slice(&decoded).map_err(|_| FastCryptoError::InvalidInput)
+}
+
+/// Extract the `iss`, `aud` and key claim from the given JWT. An `aud` given as an array is only
+/// accepted if it has exactly one element since the address is derived from a single audience.
+/// The key claim is read with [`key_claim_value`].
+pub fn extract_address_claims(
+    jwt: &str,
+    key_claim: KeyClaimName,
+) -> Result<AddressClaims, FastCryptoError> {
+    let payload = parse_jwt_payload(jwt)?;
+    let iss = string_claim(&payload, "iss")?;
+    let aud = match aud_claim(&payload)?.as_slice() {
+        [aud] => aud.clone(),
+        _ => {
+            return Err(FastCryptoError::GeneralError(
+                "Expected a single aud claim".to_string(),
+            ))
+        }
+    };
+    let key_value = key_claim_value(&payload, key_claim.as_str())?;
+    Ok(AddressClaims {
+        iss,
+        aud,
+        key_value,
+    })
+}
+
+/// Check that the `aud` claim of the given JWT, either a string or an array of strings, contains
+/// the expected audience. This should be checked before deriving an address with
+/// [`gen_address_seed`], since a different audience silently gives a different address.
+pub fn check_aud_matches(jwt: &str, expected_aud: &str) -> Result<(), FastCryptoError> {
+    let auds = aud_claim(&parse_jwt_payload(jwt)?)?;
+    match auds.iter().any(|aud| aud == expected_aud) {
+        true => Ok(()),
+        false => Err(FastCryptoError::GeneralError(format!(
+            "Expected aud {} but the JWT has aud {:?}",
+            expected_aud, auds
+        ))),
+    }
+}
+
+/// Check that the `hd` (hosted domain) claim of the given JWT equals the expected domain. The `hd`
+/// claim is specific to Google and is only set for Google Workspace accounts, so this can be used to
+/// restrict logins to a single Workspace domain. Returns [`FastCryptoError::InvalidInput`] if the
+/// claim is absent or does not match.
+pub fn check_hosted_domain(jwt: &str, expected_hd: &str) -> Result<(), FastCryptoError> {
+    match parse_jwt_payload(jwt)?.get("hd") {
+        Some(Value::String(hd)) if hd == expected_hd => Ok(()),
+        _ => Err(FastCryptoError::InvalidInput),
+    }
+}
+
+/// Check that the `azp` (authorized party) claim of the given JWT equals the expected client ID.
+/// The claim names the client the token was issued to and should be checked in addition to `aud`
+/// when the token may have been requested by a different client of the same application. Among
+/// the supported providers, it is set by Google, where it equals the OAuth client ID, and is
+/// usually absent for the others, in which case this check fails.
+pub fn check_authorized_party(jwt: &str, expected_azp: &str) -> Result<(), FastCryptoError> {
+    let azp = string_claim(&parse_jwt_payload(jwt)?, "azp")?;
+    match azp == expected_azp {
+        true => Ok(()),
+        false => Err(FastCryptoError::GeneralError(format!(
+            "Expected azp {} but the JWT has azp {}",
+            expected_azp, azp
+        ))),
+    }
+}
+
+/// Return the provider that issued the given JWT, determined by its `iss` claim with
+/// [`OIDCProvider::from_iss`]. For AWS Cognito the region and tenant are parsed from the issuer
+/// URL. The signature of the JWT is not verified.
+pub fn provider_from_jwt(jwt: &str) -> Result<OIDCProvider, FastCryptoError> {
+    OIDCProvider::from_iss(&string_claim(&parse_jwt_payload(jwt)?, "iss")?)
+}
+
+/// Return a stable identifier for the JWK with the given kid, namespaced by the provider since kids
+/// are only unique per provider, e.g. `google:6f7254101f56e41cf35c9926de84a2d552b4c6f1`. This can
+/// be used as a cache key or to log which JWK verified a token.
+///
+/// The provider part never contains `:`, so the key is split unambiguously at its first `:` even if
+/// the kid contains one. Fixed provider names are lowercased, while the region and tenant ID of an
+/// AWS tenant are kept verbatim since tenant IDs are case-sensitive, e.g.
+/// `awstenant/us-east-1/us-east-1_LPSLCkC3A:kid`.
+pub fn jwk_cache_key(provider: OIDCProvider, kid: &str) -> String {
+    format!("{}:{}", provider_slug(&provider), kid)
+}
+
+/// The provider part of [`jwk_cache_key`]. `%`, `/` and `:` in the region and tenant ID are
+/// percent-encoded, so neither the separator of the key nor that of the slug can occur in them.
+fn provider_slug(provider: &OIDCProvider) -> String {
+    match provider {
+        OIDCProvider::AwsTenant((region, tenant_id)) => {
+            let escape = |s: &str| {
+                s.replace('%', "%25")
+                    .replace('/', "%2F")
+                    .replace(':', "%3A")
+            };
+            format!("awstenant/{}/{}", escape(region), escape(tenant_id))
+        }
+        _ => provider.to_string().to_lowercase(),
+    }
+}
+
+/// Same as [`jwk_cache_key`] but identifies the JWK by its content, for JWKs without a kid. The
+/// identifier is the hex encoded Blake2b256 hash of the modulus `n` and the exponent `e`, each
+/// prefixed by its length as a big-endian u64.
+pub fn jwk_content_cache_key(provider: OIDCProvider, jwk: &JWK) -> String {
+    let mut preimage = Vec::with_capacity(16 + jwk.n.len() + jwk.e.len());
+    for value in [&jwk.n, &jwk.e] {
+        preimage.extend_from_slice(&(value.len() as u64).to_be_bytes());
+        preimage.extend_from_slice(value.as_bytes());
+    }
+    jwk_cache_key(provider, &Hex::encode(Blake2b256::digest(&preimage).digest))
+}
+
+/// Derive the address for each audience in the `aud` claim of the given JWT, using the `sub` claim
+/// as key claim. Returns (aud, address) pairs in the order the audiences appear in the token.
+pub fn candidate_addresses(
+    jwt: &str,
+    salt: &str,
+) -> Result<Vec<(String, [u8; 32])>, FastCryptoError> {
+    let payload = parse_jwt_payload(jwt)?;
+    let iss = string_claim(&payload, "iss")?;
+    let sub = key_claim_value(&payload, KeyClaimName::Sub.as_str())?;
+    aud_claim(&payload)?
+        .into_iter()
+        .map(|aud| {
+            let address_seed = gen_address_seed(salt, KeyClaimName::Sub.as_str(), &sub, &aud)?;
+            let address = get_zk_login_address(&Bn254FrElement::from_str(&address_seed)?, &iss)?;
+            Ok((aud, address))
+        })
+        .collect()
+}
+
+/// Return the value of the key claim `name` in the form hashed into the address seed by
+/// [`gen_address_seed`]. The circuit extracts the key claim from the JWT as a JSON string, so only
+/// string values are accepted. A numeric value, e.g. a numeric `sub` from some providers, is
+/// rejected instead of being formatted as a string: `"sub":123` and `"sub":"123"` are different
+/// JWTs, and no proof can be generated for the former, so an address derived from it would be
+/// unusable.
+pub fn key_claim_value(
+    payload: &serde_json::Map<String, Value>,
+    name: &str,
+) -> Result<String, FastCryptoError> {
+    match payload.get(name) {
+        Some(Value::Number(_)) => Err(FastCryptoError::GeneralError(format!(
+            "Claim {} is a number but only string key claims are supported",
+            name
+        ))),
+        _ => string_claim(payload, name),
+    }
+}
+
+/// Return the value of a string claim of the payload.
+fn string_claim(
+    payload: &serde_json::Map<String, Value>,
+    name: &str,
+) -> Result<String, FastCryptoError> {
+    match payload.get(name) {
+        Some(Value::String(value)) => Ok(value.clone()),
+        Some(_) => Err(FastCryptoError::GeneralError(format!(
+            "Claim {} is not a string",
+            name
+        ))),
+        None => Err(FastCryptoError::GeneralError(format!(
+            "Missing claim {}",
+            name
+        ))),
+    }
+}
+
+/// Return the values of the `aud` claim of the payload, which may be a string or an array of
+/// strings.
+fn aud_claim(payload: &serde_json::Map<String, Value>) -> Result<Vec<String>, FastCryptoError> {
+    match payload.get("aud") {
+        Some(Value::String(aud)) => Ok(vec![aud.clone()]),
+        Some(Value::Array(auds)) => auds
+            .iter()
+            .map(|aud| match aud {
+                Value::String(aud) => Ok(aud.clone()),
+                _ => Err(FastCryptoError::GeneralError(
+                    "Claim aud is not a string".to_string(),
+                )),
+            })
+            .collect(),
+        Some(_) => Err(FastCryptoError::GeneralError(
+            "Claim aud is not a string".to_string(),
+        )),
+        None => Err(FastCryptoError::GeneralError(
+            "Missing claim aud".to_string(),
+        )),
+    }
+}
+
+/// An ephemeral public key prefixed with the flag of its signature scheme, which is the form the
+/// nonce commits to. The length of the public key is checked against the scheme on construction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtendedEphemeralPublicKey(Vec<u8>);
+
+impl ExtendedEphemeralPublicKey {
+    /// Create from a signature scheme flag (0x00 for Ed25519, 0x01 for Secp256k1 and 0x02 for
+    /// Secp256r1) and the bytes of a public key of that scheme.
+    pub fn new(flag: u8, public_key: &[u8]) -> Result<Self, FastCryptoError> {
+        let expected_length = EphemeralKeyScheme::from_flag(flag)?.public_key_length();
+        if public_key.len() != expected_length {
+            return Err(FastCryptoError::InputLengthWrong {
+                expected: expected_length,
+                actual: public_key.len(),
+            });
+        }
+        let mut bytes = vec![flag];
+        bytes.extend_from_slice(public_key);
+        Ok(Self(bytes))
+    }
+
+    /// Parse the flag followed by the public key bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, FastCryptoError> {
+        match bytes.split_first() {
+            Some((flag, public_key)) => Self::new(*flag, public_key),
+            None => Err(FastCryptoError::InputLengthWrong {
+                expected: 1,
+                actual: 0,
+            }),
+        }
+    }
+
+    /// The flag followed by the public key bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// The signature schemes supported for ephemeral keys, identified by the flag byte preceding the
+/// public key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EphemeralKeyScheme {
+    /// Ed25519 with flag 0x00 and 32 byte public keys.
+    Ed25519,
+    /// Secp256k1 with flag 0x01 and 33 byte compressed public keys.
+    Secp256k1,
+    /// Secp256r1 with flag 0x02 and 33 byte compressed public keys.
+    Secp256r1,
+}
+
+impl EphemeralKeyScheme {
+    /// Return the scheme with the given flag or [`FastCryptoError::InvalidInput`] if the flag is
+    /// unknown.
+    pub fn from_flag(flag: u8) -> Result<Self, FastCryptoError> {
+        match flag {
+            0x00 => Ok(Self::Ed25519),
+            0x01 => Ok(Self::Secp256k1),
+            0x02 => Ok(Self::Secp256r1),
+            _ => Err(FastCryptoError::InvalidInput),
+        }
+    }
+
+    /// The flag byte of the scheme.
+    pub fn flag(&self) -> u8 {
+        match self {
+            Self::Ed25519 => 0x00,
+            Self::Secp256k1 => 0x01,
+            Self::Secp256r1 => 0x02,
+        }
+    }
+
+    /// The length in bytes of a public key of the scheme, excluding the flag.
+    pub fn public_key_length(&self) -> usize {
+        match self {
+            Self::Ed25519 => 32,
+            Self::Secp256k1 | Self::Secp256r1 => 33,
+        }
+    }
+}
+
+/// Check that the flag byte of an extended ephemeral public key (flag || pk_bytes) is known and
+/// that the length of the public key matches the scheme of the flag, and return the scheme.
+///
+/// [`get_nonce`] accepts any bytes it can split into two field elements, so a key with a wrong flag
+/// or length gives a nonce which the prover will never match. See [`get_nonce_strict`].
+pub fn validate_eph_pk_consistency(
+    eph_pk_bytes: &[u8],
+) -> Result<EphemeralKeyScheme, FastCryptoError> {
+    let key = ExtendedEphemeralPublicKey::from_bytes(eph_pk_bytes)?;
+    EphemeralKeyScheme::from_flag(key.as_bytes()[0])
 }
 
 /// Return the OIDC URL for the given parameters. Crucially the nonce is computed.
+///
+/// Prefer [`get_oidc_url_with_key`] which takes a validated ephemeral public key instead of raw
+/// bytes.
+#[cfg(feature = "client")]
 pub fn get_oidc_url(
     provider: OIDCProvider,
     eph_pk_bytes: &[u8],
@@ -72,6 +537,7 @@ pub fn get_oidc_url(
     redirect_url: &str,
     jwt_randomness: &str,
 ) -> Result<String, FastCryptoError> {
+    validate_redirect_uri(&provider, redirect_url)?;
     let nonce = get_nonce(eph_pk_bytes, max_epoch, jwt_randomness)?;
     Ok(match provider {
             OIDCProvider::Google => format!("https://accounts.google.com/o/oauth2/v2/auth?client_id={}&response_type=id_token&redirect_uri={}&scope=openid&nonce={}", client_id, redirect_url, nonce),
@@ -90,7 +556,224 @@ pub fn get_oidc_url(
     })
 }
 
+/// Same as [`get_oidc_url`] but takes the ephemeral public key as an [`ExtendedEphemeralPublicKey`].
+#[cfg(feature = "client")]
+pub fn get_oidc_url_with_key(
+    provider: OIDCProvider,
+    eph_pk: &ExtendedEphemeralPublicKey,
+    max_epoch: u64,
+    client_id: &str,
+    redirect_url: &str,
+    jwt_randomness: &str,
+) -> Result<String, FastCryptoError> {
+    get_oidc_url(
+        provider,
+        eph_pk.as_bytes(),
+        max_epoch,
+        client_id,
+        redirect_url,
+        jwt_randomness,
+    )
+}
+
+/// Builder for the OIDC URL as an alternative to [`get_oidc_url`], naming each parameter at the call
+/// site instead of passing them positionally. All fields must be set before calling
+/// [`OidcUrlBuilder::build`].
+#[cfg(feature = "client")]
+#[derive(Debug, Clone)]
+pub struct OidcUrlBuilder {
+    provider: OIDCProvider,
+    client_id: Option<String>,
+    redirect_url: Option<String>,
+    eph_pk_bytes: Option<Vec<u8>>,
+    max_epoch: Option<u64>,
+    jwt_randomness: Option<String>,
+}
+
+#[cfg(feature = "client")]
+impl OidcUrlBuilder {
+    /// Start building the OIDC URL for the given provider.
+    pub fn new(provider: OIDCProvider) -> Self {
+        Self {
+            provider,
+            client_id: None,
+            redirect_url: None,
+            eph_pk_bytes: None,
+            max_epoch: None,
+            jwt_randomness: None,
+        }
+    }
+
+    /// Set the client ID registered with the provider.
+    pub fn client_id(mut self, client_id: &str) -> Self {
+        self.client_id = Some(client_id.to_string());
+        self
+    }
+
+    /// Set the redirect URL registered with the provider.
+    pub fn redirect_url(mut self, redirect_url: &str) -> Self {
+        self.redirect_url = Some(redirect_url.to_string());
+        self
+    }
+
+    /// Set the extended ephemeral public key, i.e. flag || pk_bytes.
+    pub fn eph_pk(mut self, eph_pk_bytes: &[u8]) -> Self {
+        self.eph_pk_bytes = Some(eph_pk_bytes.to_vec());
+        self
+    }
+
+    /// Set the max epoch until which the ephemeral key is valid.
+    pub fn max_epoch(mut self, max_epoch: u64) -> Self {
+        self.max_epoch = Some(max_epoch);
+        self
+    }
+
+    /// Set the randomness committed to in the nonce.
+    pub fn jwt_randomness(mut self, jwt_randomness: &str) -> Self {
+        self.jwt_randomness = Some(jwt_randomness.to_string());
+        self
+    }
+
+    /// Return the OIDC URL computed with [`get_oidc_url`]. If any field is not set, an error naming
+    /// all missing fields is returned.
+    pub fn build(&self) -> Result<String, FastCryptoError> {
+        match (
+            &self.client_id,
+            &self.redirect_url,
+            &self.eph_pk_bytes,
+            self.max_epoch,
+            &self.jwt_randomness,
+        ) {
+            (
+                Some(client_id),
+                Some(redirect_url),
+                Some(eph_pk_bytes),
+                Some(max_epoch),
+                Some(jwt_randomness),
+            ) => get_oidc_url(
+                self.provider.clone(),
+                eph_pk_bytes,
+                max_epoch,
+                client_id,
+                redirect_url,
+                jwt_randomness,
+            ),
+            _ => {
+                let missing: Vec<&str> = [
+                    ("client_id", self.client_id.is_none()),
+                    ("redirect_url", self.redirect_url.is_none()),
+                    ("eph_pk", self.eph_pk_bytes.is_none()),
+                    ("max_epoch", self.max_epoch.is_none()),
+                    ("jwt_randomness", self.jwt_randomness.is_none()),
+                ]
+                .into_iter()
+                .filter_map(|(name, is_missing)| is_missing.then_some(name))
+                .collect();
+                Err(FastCryptoError::GeneralError(format!(
+                    "Missing fields for OIDC URL: {}",
+                    missing.join(", ")
+                )))
+            }
+        }
+    }
+}
+
+/// Check that the redirect URI is one the provider will accept: it must be a valid absolute URL
+/// using https (plain http is only allowed for localhost, and never for Apple), without a fragment
+/// and without wildcards. Returns an error describing the first violated constraint.
+#[cfg(feature = "client")]
+pub fn validate_redirect_uri(
+    provider: &OIDCProvider,
+    redirect_url: &str,
+) -> Result<(), FastCryptoError> {
+    let url = reqwest::Url::parse(redirect_url).map_err(|e| {
+        FastCryptoError::GeneralError(format!("Invalid redirect URI {}: {}", redirect_url, e))
+    })?;
+    if url.fragment().is_some() {
+        return Err(FastCryptoError::GeneralError(
+            "Redirect URI must not contain a fragment".to_string(),
+        ));
+    }
+    if redirect_url.contains('*') {
+        return Err(FastCryptoError::GeneralError(
+            "Redirect URI must not contain wildcards".to_string(),
+        ));
+    }
+    let is_localhost = matches!(
+        url.host_str(),
+        Some("localhost") | Some("127.0.0.1") | Some("[::1]")
+    );
+    match (url.scheme(), provider) {
+        ("https", _) => Ok(()),
+        ("http", OIDCProvider::Apple) => Err(FastCryptoError::GeneralError(
+            "Apple requires an https redirect URI".to_string(),
+        )),
+        ("http", _) if is_localhost => Ok(()),
+        _ => Err(FastCryptoError::GeneralError(
+            "Redirect URI must use https unless it points to localhost".to_string(),
+        )),
+    }
+}
+
+/// The zkLogin configuration of a backend.
+#[cfg(feature = "client")]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ZkLoginConfig {
+    /// The name of the OIDC provider as accepted by [`OIDCProvider::from_str`], e.g. "Google".
+    pub provider: String,
+    /// The client ID registered with the provider.
+    pub client_id: String,
+    /// The redirect URI registered with the provider.
+    pub redirect_url: String,
+    /// The URL of the salt server.
+    pub salt_url: String,
+    /// The URL of the prover.
+    pub prover_url: String,
+}
+
+#[cfg(feature = "client")]
+impl ZkLoginConfig {
+    /// Run all local checks on the configuration: that the provider is supported, that the client
+    /// ID is well-formed, that the redirect URI is accepted by [`validate_redirect_uri`] and that the
+    /// salt server and prover URLs are valid http(s) URLs. All problems are returned at once. No
+    /// network requests are made.
+    pub fn validate(&self) -> Result<(), Vec<FastCryptoError>> {
+        let mut errors = Vec::new();
+        match OIDCProvider::from_str(&self.provider) {
+            Ok(provider) => {
+                if let Err(e) = validate_redirect_uri(&provider, &self.redirect_url) {
+                    errors.push(e);
+                }
+            }
+            Err(_) => errors.push(FastCryptoError::GeneralError(format!(
+                "Unsupported provider {}",
+                self.provider
+            ))),
+        }
+        if self.client_id.is_empty() || self.client_id.contains(char::is_whitespace) {
+            errors.push(FastCryptoError::GeneralError(format!(
+                "Invalid client ID {:?}",
+                self.client_id
+            )));
+        }
+        for (name, url) in [("salt", &self.salt_url), ("prover", &self.prover_url)] {
+            match reqwest::Url::parse(url) {
+                Ok(url) if url.scheme() == "https" || url.scheme() == "http" => {}
+                _ => errors.push(FastCryptoError::GeneralError(format!(
+                    "Invalid {} URL {}",
+                    name, url
+                ))),
+            }
+        }
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors),
+        }
+    }
+}
+
 /// Return the token exchange URL for the given auth code.
+#[cfg(feature = "client")]
 pub fn get_token_exchange_url(
     provider: OIDCProvider,
     client_id: &str,
@@ -107,29 +790,452 @@ pub fn get_token_exchange_url(
 
 /// Calculate the nonce for the given parameters. Nonce is defined as the Base64Url encoded of the poseidon hash of 4 inputs:
 /// first half of eph_pk_bytes in BigInt, second half of eph_pk_bytes in BigInt, max_epoch and jwt_randomness.
+/// The nonce is always encoded without padding.
 pub fn get_nonce(
     eph_pk_bytes: &[u8],
     max_epoch: u64,
     jwt_randomness: &str,
 ) -> Result<String, FastCryptoError> {
+    get_nonce_with_policy(
+        eph_pk_bytes,
+        max_epoch,
+        jwt_randomness,
+        NoncePolicy::default(),
+    )
+}
+
+/// Same as [`get_nonce`] but first checks that the ephemeral public key is consistent with its
+/// flag using [`validate_eph_pk_consistency`].
+pub fn get_nonce_strict(
+    eph_pk_bytes: &[u8],
+    max_epoch: u64,
+    jwt_randomness: &str,
+) -> Result<String, FastCryptoError> {
+    validate_eph_pk_consistency(eph_pk_bytes)?;
+    get_nonce(eph_pk_bytes, max_epoch, jwt_randomness)
+}
+
+/// The number of bytes of the poseidon hash encoded in a nonce by the production circuit.
+pub const NONCE_BYTES_LENGTH: usize = 20;
+
+/// Return the bytes of the nonce hash that are encoded in a nonce by [`get_nonce`]. The hash is
+/// serialized as a 32 byte big-endian integer and the last 20 bytes are kept, i.e. the 160 least
+/// significant bits. This must match the circuit exactly, since any other choice gives a nonce that
+/// silently differs from the one in the JWT.
+pub fn hash_to_nonce_bytes(hash: Bn254Fr) -> [u8; NONCE_BYTES_LENGTH] {
+    truncate_hash(&hash_to_be_bytes(&hash), NONCE_BYTES_LENGTH)
+        .try_into()
+        .expect("length is smaller than 32")
+}
+
+/// Keep the last `length` bytes of a 32 byte big-endian hash. The caller ensures that
+/// `length <= 32`, see [`NoncePolicy`].
+fn truncate_hash(data: &[u8; 32], length: usize) -> &[u8] {
+    &data[32 - length..]
+}
+
+/// Serialize a field element as a 32 byte big-endian integer.
+fn hash_to_be_bytes(hash: &Bn254Fr) -> [u8; 32] {
+    Bn254FrElement::from(hash)
+        .padded()
+        .try_into()
+        .expect("padded field elements are 32 bytes")
+}
+
+/// Policy for how many bytes of the poseidon hash are kept when encoding a nonce.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoncePolicy {
+    /// Keep the last 20 bytes. This is what the production circuit expects.
+    Truncate20,
+    /// Keep the full 32 bytes.
+    Full32,
+    /// Keep the last n bytes, where 0 < n <= 32.
+    TruncateN(usize),
+}
+
+impl Default for NoncePolicy {
+    fn default() -> Self {
+        Self::Truncate20
+    }
+}
+
+impl NoncePolicy {
+    fn length(&self) -> Result<usize, FastCryptoError> {
+        match self {
+            NoncePolicy::Truncate20 => Ok(NONCE_BYTES_LENGTH),
+            NoncePolicy::Full32 => Ok(32),
+            NoncePolicy::TruncateN(n) if (1..=32).contains(n) => Ok(*n),
+            NoncePolicy::TruncateN(_) => Err(FastCryptoError::InvalidInput),
+        }
+    }
+}
+
+/// Same as [`get_nonce`] but truncates the poseidon hash according to the given policy. This is
+/// only useful for circuits other than the production one, which uses [`NoncePolicy::Truncate20`].
+pub fn get_nonce_with_policy(
+    eph_pk_bytes: &[u8],
+    max_epoch: u64,
+    jwt_randomness: &str,
+    policy: NoncePolicy,
+) -> Result<String, FastCryptoError> {
+    nonce_with_hash(eph_pk_bytes, max_epoch, jwt_randomness, policy).map(|(_, nonce)| nonce)
+}
+
+/// Same as [`get_nonce`] but also returns the full 32 byte big-endian poseidon hash that the nonce
+/// is a truncation of, for circuits that need both.
+pub fn get_nonce_full(
+    eph_pk_bytes: &[u8],
+    max_epoch: u64,
+    jwt_randomness: &str,
+) -> Result<([u8; 32], String), FastCryptoError> {
+    nonce_with_hash(
+        eph_pk_bytes,
+        max_epoch,
+        jwt_randomness,
+        NoncePolicy::default(),
+    )
+}
+
+fn nonce_with_hash(
+    eph_pk_bytes: &[u8],
+    max_epoch: u64,
+    jwt_randomness: &str,
+    policy: NoncePolicy,
+) -> Result<([u8; 32], String), FastCryptoError> {
     let (first, second) = split_to_two_frs(eph_pk_bytes)?;
 
-    let max_epoch = Bn254Fr::from_str(&max_epoch.to_string())
-        .expect("max_epoch.to_string is always non empty string without trailing zeros");
     let jwt_randomness =
         Bn254Fr::from_str(jwt_randomness).map_err(|_| FastCryptoError::InvalidInput)?;
+    // Any u64, including u64::MAX, is smaller than the ~254 bit modulus of the scalar field, so the
+    // conversion is exact and cannot fail.
+    nonce_from_field_elements(
+        first,
+        second,
+        Bn254Fr::from(max_epoch),
+        jwt_randomness,
+        policy,
+    )
+}
 
+/// Calculate the nonces for all max_epochs in the given range, reusing the parsed ephemeral public
+/// key and randomness. Returns (max_epoch, nonce) pairs in increasing epoch order. An empty or
+/// reversed range gives an empty result.
+pub fn gen_nonces_for_epoch_range(
+    eph_pk_bytes: &[u8],
+    epochs: Range<u64>,
+    jwt_randomness: &str,
+) -> Result<Vec<(u64, String)>, FastCryptoError> {
+    let (first, second) = split_to_two_frs(eph_pk_bytes)?;
+    let jwt_randomness =
+        Bn254Fr::from_str(jwt_randomness).map_err(|_| FastCryptoError::InvalidInput)?;
+    epochs
+        .map(|epoch| {
+            let nonce = nonce_from_field_elements(
+                first,
+                second,
+                Bn254Fr::from(epoch),
+                jwt_randomness,
+                NoncePolicy::default(),
+            )?
+            .1;
+            Ok((epoch, nonce))
+        })
+        .collect()
+}
+
+/// Compute the poseidon hash of the nonce inputs and return it along with its Base64Url encoding,
+/// truncated according to the policy.
+fn nonce_from_field_elements(
+    first: Bn254Fr,
+    second: Bn254Fr,
+    max_epoch: Bn254Fr,
+    jwt_randomness: Bn254Fr,
+    policy: NoncePolicy,
+) -> Result<([u8; 32], String), FastCryptoError> {
+    let length = policy.length()?;
     let hash = poseidon_zk_login(&[first, second, max_epoch, jwt_randomness])
         .expect("inputs is not too long");
-    let data = BigUint::from(hash).to_bytes_be();
-    let truncated = &data[data.len() - 20..];
+    let data = hash_to_be_bytes(&hash);
+    let truncated = truncate_hash(&data, length);
     let mut buf = vec![0; Base64UrlUnpadded::encoded_len(truncated)];
-    Ok(Base64UrlUnpadded::encode(truncated, &mut buf)
+    let nonce = Base64UrlUnpadded::encode(truncated, &mut buf)
         .unwrap()
-        .to_string())
+        .to_string();
+    Ok((data, nonce))
+}
+
+/// Start a new session for an existing address with a new ephemeral key, e.g. when the previous
+/// one has expired, and return the nonce for the next OIDC login, see [`get_nonce`]. The address is
+/// derived from the JWT claims and the salt only, so it stays the same across sessions while the
+/// ephemeral key, max_epoch and hence the nonce change. The old address is not used to compute the
+/// nonce and is only taken to make this explicit at the call site.
+pub fn derive_new_session(
+    _old_address: [u8; 32],
+    new_eph_pk_bytes: &[u8],
+    max_epoch: u64,
+    jwt_randomness: &str,
+) -> Result<String, FastCryptoError> {
+    get_nonce(new_eph_pk_bytes, max_epoch, jwt_randomness)
+}
+
+/// Generate a fresh jwt_randomness with the given rng, i.e. a uniformly random element of the BN254
+/// scalar field encoded as a canonical decimal string. The same randomness must be used both when
+/// computing the nonce with [`get_nonce`] and when requesting the proof with `get_proof`.
+pub fn gen_jwt_randomness<R: AllowedRng>(rng: &mut R) -> String {
+    fr_to_decimal_string(&Bn254Fr::rand(rng))
+}
+
+/// Verify that a nonce claim, e.g. the `nonce` claim of a JWT, equals the nonce computed with
+/// [`get_nonce`] from the given parameters. While [`get_nonce`] always produces unpadded output,
+/// some providers echo the nonce back padded. A 20 byte nonce encodes to 27 Base64 characters, so
+/// its padding is a single `=`, and the claim is accepted either unpadded or with exactly one
+/// trailing `=`.
+pub fn verify_nonce(
+    nonce_claim: &str,
+    eph_pk_bytes: &[u8],
+    max_epoch: u64,
+    jwt_randomness: &str,
+) -> Result<(), FastCryptoError> {
+    let expected = get_nonce(eph_pk_bytes, max_epoch, jwt_randomness)?;
+    let unpadded = nonce_claim.strip_suffix('=').unwrap_or(nonce_claim);
+    match unpadded == expected {
+        true => Ok(()),
+        false => Err(FastCryptoError::GeneralError("Nonce mismatch".to_string())),
+    }
+}
+
+/// The JSON pointer to the `nonce` claim in a standard JWT payload.
+pub const DEFAULT_NONCE_POINTER: &str = "/nonce";
+
+/// Verify the nonce claim of the given JWT with [`verify_nonce`]. The claim is located with the
+/// JSON pointer `nonce_pointer` (RFC 6901) into the payload, which is [`DEFAULT_NONCE_POINTER`]
+/// for standard tokens but allows e.g. `/claims/nonce` for proxies that nest the claim. The
+/// signature of the JWT is not verified.
+pub fn verify_jwt_nonce(
+    jwt: &str,
+    nonce_pointer: &str,
+    eph_pk_bytes: &[u8],
+    max_epoch: u64,
+    jwt_randomness: &str,
+) -> Result<(), FastCryptoError> {
+    let payload = Value::Object(parse_jwt_payload(jwt)?);
+    match payload.pointer(nonce_pointer) {
+        Some(Value::String(nonce_claim)) => {
+            verify_nonce(nonce_claim, eph_pk_bytes, max_epoch, jwt_randomness)
+        }
+        Some(_) => Err(FastCryptoError::GeneralError(format!(
+            "Nonce at {} is not a string",
+            nonce_pointer
+        ))),
+        None => Err(FastCryptoError::GeneralError(format!(
+            "Missing nonce at {}",
+            nonce_pointer
+        ))),
+    }
+}
+
+/// Check that a proof from the prover was generated for the given JWT and that the JWT's `nonce`
+/// claim commits to the given max_epoch. A [`ZkLoginInputsReader`] does not carry max_epoch, which
+/// only enters the public inputs hash, so it cannot be read from the proof and must be supplied by
+/// the caller. Without this check, a proof requested for a different max_epoch than the one of the
+/// login session is only detected when the proof fails to verify. The JWT is matched by its header,
+/// which is part of the proof inputs, and the nonce is checked with [`verify_jwt_nonce`].
+pub fn verify_max_epoch_consistency(
+    jwt: &str,
+    reader: &ZkLoginInputsReader,
+    max_epoch: u64,
+    eph_pk_bytes: &[u8],
+    jwt_randomness: &str,
+) -> Result<(), FastCryptoError> {
+    if jwt.split('.').next() != Some(reader.get_header_base64()) {
+        return Err(FastCryptoError::GeneralError(
+            "The proof was not generated for this JWT".to_string(),
+        ));
+    }
+    verify_jwt_nonce(
+        jwt,
+        DEFAULT_NONCE_POINTER,
+        eph_pk_bytes,
+        max_epoch,
+        jwt_randomness,
+    )
+}
+
+/// Check that a proof from the prover is consistent with the JWT, the salt and the ephemeral
+/// parameters it was requested for, and return the address it proves ownership of. The proof must
+/// have been generated for the JWT and its nonce must commit to the ephemeral parameters, see
+/// [`verify_max_epoch_consistency`], and the iss the proof commits to must be the `iss` claim of
+/// the JWT. The response of the prover does not contain the address seed, so it is derived from
+/// the `sub`, `aud` and `iss` claims and the salt as in [`candidate_addresses`].
+///
+/// This does not verify the proof itself, which requires the JWK of the provider, see
+/// [`crate::bn254::zk_login_api::verify_zk_login`].
+pub fn verify_proof_bundle(
+    jwt: &str,
+    salt: &str,
+    eph_pk_bytes: &[u8],
+    max_epoch: u64,
+    jwt_randomness: &str,
+    reader: &ZkLoginInputsReader,
+) -> Result<[u8; 32], FastCryptoError> {
+    verify_max_epoch_consistency(jwt, reader, max_epoch, eph_pk_bytes, jwt_randomness)?;
+    let claims = extract_address_claims(jwt, KeyClaimName::Sub)?;
+    let proof_iss = reader.get_jwk_id()?.iss;
+    if proof_iss != claims.iss {
+        return Err(FastCryptoError::GeneralError(format!(
+            "Expected iss {} but the proof has iss {}",
+            claims.iss, proof_iss
+        )));
+    }
+    let address_seed = gen_address_seed(
+        salt,
+        KeyClaimName::Sub.as_str(),
+        &claims.key_value,
+        &claims.aud,
+    )?;
+    get_zk_login_address(&Bn254FrElement::from_str(&address_seed)?, &claims.iss)
+}
+
+/// The serialized form of a zkLogin authenticator, excluding the flag.
+#[derive(Serialize)]
+struct ZkLoginAuthenticatorBytes<'a> {
+    inputs: &'a ZkLoginInputs,
+    max_epoch: u64,
+    user_signature: Vec<u8>,
+}
+
+/// Assemble the bytes of a zkLogin signature as verified on-chain: the zkLogin flag followed by the
+/// BCS serialization of the zkLogin inputs, max_epoch and the user signature. The user signature is
+/// the flag of the ephemeral key followed by the signature and the public key.
+///
+/// The inputs must contain the address seed, so a [`ZkLoginInputsReader`] from the prover must be
+/// converted with [`ZkLoginInputs::from_reader`] first. The ephemeral public key is given as
+/// flag || pk_bytes and is checked with [`validate_eph_pk_consistency`].
+pub fn assemble_zk_login_signature(
+    inputs: &ZkLoginInputs,
+    max_epoch: u64,
+    eph_signature: &[u8],
+    eph_pk_bytes: &[u8],
+) -> Result<Vec<u8>, FastCryptoError> {
+    validate_eph_pk_consistency(eph_pk_bytes)?;
+    if eph_signature.len() != EPH_SIGNATURE_LENGTH {
+        return Err(FastCryptoError::InputLengthWrong {
+            expected: EPH_SIGNATURE_LENGTH,
+            actual: eph_signature.len(),
+        });
+    }
+
+    let (flag, public_key) = eph_pk_bytes.split_at(1);
+    let mut user_signature = flag.to_vec();
+    user_signature.extend_from_slice(eph_signature);
+    user_signature.extend_from_slice(public_key);
+
+    let mut bytes = vec![ZK_LOGIN_AUTHENTICATOR_FLAG];
+    bytes.extend(
+        bcs::to_bytes(&ZkLoginAuthenticatorBytes {
+            inputs,
+            max_epoch,
+            user_signature,
+        })
+        .map_err(|_| FastCryptoError::InvalidInput)?,
+    );
+    Ok(bytes)
+}
+
+/// The components of a zkLogin signature, see [`parse_zk_login_signature`].
+#[derive(Debug, Clone)]
+pub struct ParsedZkLoginSig {
+    /// The zkLogin inputs, including the proof and the address seed.
+    pub inputs: ZkLoginInputs,
+    /// The max epoch until which the ephemeral key is valid.
+    pub max_epoch: u64,
+    /// The signature by the ephemeral key.
+    pub eph_signature: Vec<u8>,
+    /// The ephemeral public key as flag || pk_bytes.
+    pub eph_pk_bytes: Vec<u8>,
+}
+
+/// The deserialized form of [`ZkLoginAuthenticatorBytes`].
+#[derive(Deserialize)]
+struct ZkLoginAuthenticator {
+    inputs: ZkLoginInputs,
+    max_epoch: u64,
+    user_signature: Vec<u8>,
+}
+
+/// Parse the bytes of a zkLogin signature as assembled by [`assemble_zk_login_signature`], e.g. to
+/// inspect a signature taken from the chain. The flag, the layout of the user signature and the
+/// ephemeral public key are validated, but the signature and the proof are not verified.
+pub fn parse_zk_login_signature(bytes: &[u8]) -> Result<ParsedZkLoginSig, FastCryptoError> {
+    match bytes.split_first() {
+        Some((&ZK_LOGIN_AUTHENTICATOR_FLAG, _)) => (),
+        Some(_) => return Err(FastCryptoError::InvalidInput),
+        None => {
+            return Err(FastCryptoError::InputLengthWrong {
+                expected: 1,
+                actual: 0,
+            })
+        }
+    }
+    let mut authenticator: ZkLoginAuthenticator =
+        bcs::from_bytes(&bytes[1..]).map_err(|_| FastCryptoError::InvalidInput)?;
+
+    let user_signature = authenticator.user_signature;
+    if user_signature.len() <= 1 + EPH_SIGNATURE_LENGTH {
+        return Err(FastCryptoError::InputLengthWrong {
+            expected: 2 + EPH_SIGNATURE_LENGTH,
+            actual: user_signature.len(),
+        });
+    }
+    let (eph_signature, public_key) = user_signature[1..].split_at(EPH_SIGNATURE_LENGTH);
+    let mut eph_pk_bytes = vec![user_signature[0]];
+    eph_pk_bytes.extend_from_slice(public_key);
+    validate_eph_pk_consistency(&eph_pk_bytes)?;
+
+    Ok(ParsedZkLoginSig {
+        inputs: authenticator.inputs.init()?,
+        max_epoch: authenticator.max_epoch,
+        eph_signature: eph_signature.to_vec(),
+        eph_pk_bytes,
+    })
+}
+
+/// Verify a signature by an ephemeral key, e.g. the ephemeral signature of a zkLogin signature. The
+/// public key is given as flag || pk_bytes, and the signature is verified with the scheme of the
+/// flag, i.e. the same scheme the nonce was derived for. Verifying the proof is left to
+/// [`crate::bn254::zk_login_api::verify_zk_login`].
+pub fn verify_ephemeral_signature(
+    eph_pk_bytes: &[u8],
+    message: &[u8],
+    signature: &[u8],
+) -> Result<(), FastCryptoError> {
+    let scheme = validate_eph_pk_consistency(eph_pk_bytes)?;
+    let public_key = &eph_pk_bytes[1..];
+    match scheme {
+        EphemeralKeyScheme::Ed25519 => {
+            verify_signature::<Ed25519PublicKey>(public_key, message, signature)
+        }
+        EphemeralKeyScheme::Secp256k1 => {
+            verify_signature::<Secp256k1PublicKey>(public_key, message, signature)
+        }
+        EphemeralKeyScheme::Secp256r1 => {
+            verify_signature::<Secp256r1PublicKey>(public_key, message, signature)
+        }
+    }
+}
+
+fn verify_signature<PK: VerifyingKey>(
+    public_key: &[u8],
+    message: &[u8],
+    signature: &[u8],
+) -> Result<(), FastCryptoError> {
+    let public_key = PK::from_bytes(public_key)?;
+    let signature = <PK::Sig as ToFromBytes>::from_bytes(signature)?;
+    public_key.verify(message, &signature)
 }
 
 /// A response struct for the salt server.
+#[cfg(feature = "client")]
 #[derive(Deserialize, Debug)]
 pub struct GetSaltResponse {
     /// The salt in BigInt string.
@@ -137,16 +1243,145 @@ pub struct GetSaltResponse {
 }
 
 /// Call the salt server for the given jwt_token and return the salt.
+#[cfg(feature = "client")]
 pub async fn get_salt(jwt_token: &str, salt_url: &str) -> Result<String, FastCryptoError> {
+    get_salt_with_options(jwt_token, salt_url, &SaltRequestOptions::default()).await
+}
+
+/// The format of requests to the salt server. The defaults give the request sent by [`get_salt`],
+/// i.e. `{ "token": jwt_token }` with no additional headers.
+#[cfg(feature = "client")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaltRequestOptions {
+    /// The name of the field of the request body holding the JWT.
+    pub token_field: String,
+    /// Headers added to the request, e.g. an API key required by the salt server.
+    pub extra_headers: Vec<(String, String)>,
+}
+
+#[cfg(feature = "client")]
+impl Default for SaltRequestOptions {
+    fn default() -> Self {
+        Self {
+            token_field: "token".to_string(),
+            extra_headers: vec![],
+        }
+    }
+}
+
+/// Same as [`get_salt`] but with the request format given by the options, for salt servers with an
+/// API other than that of the reference salt server.
+#[cfg(feature = "client")]
+pub async fn get_salt_with_options(
+    jwt_token: &str,
+    salt_url: &str,
+    options: &SaltRequestOptions,
+) -> Result<String, FastCryptoError> {
     let client = Client::new();
-    let body = json!({ "token": jwt_token });
-    let response = client
-        .post(salt_url)
-        .json(&body)
-        .header("Content-Type", "application/json")
-        .send()
+    let response = send_salt_request(&client, jwt_token, salt_url, options)
         .await
         .map_err(|_| FastCryptoError::InvalidInput)?;
+    salt_from_response(response).await
+}
+
+/// Options for retrying requests to the salt server in [`get_salt_with_retry`].
+#[cfg(feature = "client")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaltRetryOptions {
+    /// The maximum number of requests sent, including the first one. Must be positive.
+    pub max_attempts: u32,
+    /// The backoff before the first retry. It is doubled for every following retry, and a random
+    /// jitter of up to half the backoff is subtracted.
+    pub initial_backoff: Duration,
+    /// The format of the requests, see [`get_salt_with_options`].
+    pub request: SaltRequestOptions,
+}
+
+#[cfg(feature = "client")]
+impl Default for SaltRetryOptions {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(200),
+            request: SaltRequestOptions::default(),
+        }
+    }
+}
+
+#[cfg(feature = "client")]
+impl SaltRetryOptions {
+    fn backoff(&self, retry: u32) -> Duration {
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(1 << retry.min(MAX_BACKOFF_DOUBLINGS));
+        backoff.saturating_sub(backoff.mul_f64(rand::thread_rng().gen_range(0.0..0.5)))
+    }
+}
+
+/// Same as [`get_salt_with_options`], but requests which fail with a server error (5xx), a timeout or a
+/// connection error, e.g. while the salt server is being deployed, are retried with a jittered
+/// exponential backoff. Client errors (4xx) are not retried. If all attempts fail, the error of the
+/// last attempt is returned.
+#[cfg(feature = "client")]
+pub async fn get_salt_with_retry(
+    jwt_token: &str,
+    salt_url: &str,
+    options: &SaltRetryOptions,
+) -> Result<String, FastCryptoError> {
+    if options.max_attempts == 0 {
+        return Err(FastCryptoError::InvalidInput);
+    }
+    let client = Client::new();
+    let mut retry = 0;
+    loop {
+        let error = match send_salt_request(&client, jwt_token, salt_url, &options.request).await {
+            Ok(response) if response.status().is_success() => {
+                return salt_from_response(response).await
+            }
+            Ok(response) => {
+                let error = FastCryptoError::GeneralError(format!(
+                    "Salt server returned {}",
+                    response.status()
+                ));
+                if !response.status().is_server_error() {
+                    return Err(error);
+                }
+                error
+            }
+            Err(e) if e.is_connect() || e.is_timeout() => {
+                FastCryptoError::GeneralError(format!("Salt request failed: {}", e))
+            }
+            Err(_) => return Err(FastCryptoError::InvalidInput),
+        };
+        retry += 1;
+        if retry >= options.max_attempts {
+            return Err(error);
+        }
+        tokio::time::sleep(options.backoff(retry - 1)).await;
+    }
+}
+
+#[cfg(feature = "client")]
+async fn send_salt_request(
+    client: &Client,
+    jwt_token: &str,
+    salt_url: &str,
+    options: &SaltRequestOptions,
+) -> Result<reqwest::Response, reqwest::Error> {
+    let mut body = serde_json::Map::new();
+    body.insert(options.token_field.clone(), json!(jwt_token));
+    let mut request = client
+        .post(salt_url)
+        .json(&body)
+        .header("Content-Type", "application/json");
+    for (name, value) in &options.extra_headers {
+        request = request.header(name, value);
+    }
+    request.send().await
+}
+
+#[cfg(feature = "client")]
+async fn salt_from_response(response: reqwest::Response) -> Result<String, FastCryptoError> {
     let full_bytes = response
         .bytes()
         .await
@@ -156,7 +1391,51 @@ pub async fn get_salt(jwt_token: &str, salt_url: &str) -> Result<String, FastCry
     Ok(res.salt)
 }
 
+/// Check the parameters of a proof request locally, so malformed requests fail without a round
+/// trip to the prover. The checks are, in order:
+/// - the JWT has three parts and a JSON payload with string `iss`, `sub` and `nonce` claims,
+/// - max_epoch is positive,
+/// - jwt_randomness and salt are canonical decimal field elements,
+/// - eph_pubkey is the canonical decimal encoding of the extended ephemeral public key, i.e. of the
+///   integer with big-endian bytes flag || pk_bytes, which can be split as in [`split_to_two_frs`].
+///
+/// The error is `Invalid <name>` for the first invalid parameter, or `Invalid jwt claim <claim>` for
+/// a missing or non-string claim. The signature of the JWT is not verified and the
+/// nonce is not checked against the other parameters, see [`verify_jwt_nonce`].
+pub fn validate_proof_request(
+    jwt_token: &str,
+    max_epoch: u64,
+    jwt_randomness: &str,
+    eph_pubkey: &str,
+    salt: &str,
+) -> Result<(), FastCryptoError> {
+    let invalid = |name: &str| FastCryptoError::GeneralError(format!("Invalid {}", name));
+
+    let payload = parse_jwt_payload(jwt_token).map_err(|_| invalid("jwt"))?;
+    for claim in ["iss", "sub", "nonce"] {
+        string_claim(&payload, claim).map_err(|_| invalid(&format!("jwt claim {}", claim)))?;
+    }
+    if max_epoch == 0 {
+        return Err(invalid("max_epoch"));
+    }
+    fr_from_decimal_string(jwt_randomness).map_err(|_| invalid("jwt_randomness"))?;
+    fr_from_decimal_string(salt).map_err(|_| invalid("salt"))?;
+
+    if eph_pubkey.is_empty()
+        || !eph_pubkey.bytes().all(|b| b.is_ascii_digit())
+        || eph_pubkey.starts_with('0')
+    {
+        return Err(invalid("eph_pubkey"));
+    }
+    let eph_pk_bytes = BigUint::parse_bytes(eph_pubkey.as_bytes(), 10)
+        .ok_or_else(|| invalid("eph_pubkey"))?
+        .to_bytes_be();
+    split_to_two_frs(&eph_pk_bytes).map_err(|_| invalid("eph_pubkey"))?;
+    Ok(())
+}
+
 /// Call the prover backend to get the zkLogin inputs based on jwt_token, max_epoch, jwt_randomness, eph_pubkey and salt.
+#[cfg(feature = "client")]
 pub async fn get_proof(
     jwt_token: &str,
     max_epoch: u64,
@@ -165,19 +1444,154 @@ pub async fn get_proof(
     salt: &str,
     prover_url: &str,
 ) -> Result<ZkLoginInputsReader, FastCryptoError> {
-    let body = json!({
-    "jwt": jwt_token,
-    "extendedEphemeralPublicKey": eph_pubkey,
-    "maxEpoch": max_epoch,
-    "jwtRandomness": jwt_randomness,
-    "salt": salt,
-    "keyClaimName": "sub",
-    });
+    get_proof_with_options(
+        jwt_token,
+        max_epoch,
+        jwt_randomness,
+        eph_pubkey,
+        salt,
+        prover_url,
+        &ProofRequestOptions::default(),
+    )
+    .await
+}
+
+/// The naming convention of the JSON fields of a prover deployment. The reference prover uses
+/// camelCase, but some forks use snake_case and silently ignore fields named otherwise. Responses
+/// are accepted in both conventions regardless of the dialect.
+#[cfg(feature = "client")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProverDialect {
+    /// Fields such as `maxEpoch` and `jwtRandomness`.
+    #[default]
+    CamelCase,
+    /// Fields such as `max_epoch` and `jwt_randomness`.
+    SnakeCase,
+}
+
+#[cfg(feature = "client")]
+impl ProverDialect {
+    /// Return the name of a request field in this dialect, given its camelCase name.
+    fn field_name(&self, camel_case: &str) -> String {
+        match self {
+            Self::CamelCase => camel_case.to_string(),
+            Self::SnakeCase => camel_case.chars().fold(String::new(), |mut name, c| {
+                if c.is_ascii_uppercase() {
+                    name.push('_');
+                }
+                name.push(c.to_ascii_lowercase());
+                name
+            }),
+        }
+    }
+}
+
+/// Optional parameters of a proof request. The defaults give the request sent by [`get_proof`].
+#[cfg(feature = "client")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProofRequestOptions {
+    /// The circuit version to request a proof for. Sent as the `circuitVersion` field of the request
+    /// body, following the naming of the other fields, and omitted if `None` so the prover uses its
+    /// default circuit.
+    pub circuit_version: Option<u32>,
+    /// The naming convention of the fields of the request body.
+    pub dialect: ProverDialect,
+}
+
+/// Same as [`get_proof`] but with additional options for the request.
+#[cfg(feature = "client")]
+pub async fn get_proof_with_options(
+    jwt_token: &str,
+    max_epoch: u64,
+    jwt_randomness: &str,
+    eph_pubkey: &str,
+    salt: &str,
+    prover_url: &str,
+    options: &ProofRequestOptions,
+) -> Result<ZkLoginInputsReader, FastCryptoError> {
+    let body = proof_request_body(
+        jwt_token,
+        max_epoch,
+        jwt_randomness,
+        eph_pubkey,
+        salt,
+        options,
+    );
+    let full_bytes = send_proof_request(&body, prover_url).await?;
+    let get_proof_response: ZkLoginInputsReader =
+        serde_json::from_slice(&full_bytes).map_err(|_| FastCryptoError::InvalidInput)?;
+    Ok(get_proof_response)
+}
+
+/// Metadata returned by the prover alongside the proof. All fields are optional since not every
+/// prover deployment returns them.
+#[cfg(feature = "client")]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProofMetadata {
+    /// The time it took the prover to generate the proof in milliseconds.
+    #[serde(alias = "generation_time_ms")]
+    pub generation_time_ms: Option<u64>,
+    /// The version of the circuit the proof was generated for.
+    #[serde(alias = "circuit_version")]
+    pub circuit_version: Option<u32>,
+    /// Any warnings returned by the prover.
+    #[serde(default)]
+    pub warnings: Vec<String>,
+}
+
+/// The full response of the prover, i.e. the zkLogin inputs and the metadata about the proof.
+#[cfg(feature = "client")]
+#[derive(Debug, Clone)]
+pub struct ProofResponse {
+    /// The zkLogin inputs as returned by [`get_proof`].
+    pub inputs: ZkLoginInputsReader,
+    /// The metadata returned along with the proof.
+    pub metadata: ProofMetadata,
+}
+
+/// Same as [`get_proof_with_options`] but also returns the metadata of the prover response which
+/// is otherwise discarded.
+#[cfg(feature = "client")]
+pub async fn get_proof_with_metadata(
+    jwt_token: &str,
+    max_epoch: u64,
+    jwt_randomness: &str,
+    eph_pubkey: &str,
+    salt: &str,
+    prover_url: &str,
+    options: &ProofRequestOptions,
+) -> Result<ProofResponse, FastCryptoError> {
+    let body = proof_request_body(
+        jwt_token,
+        max_epoch,
+        jwt_randomness,
+        eph_pubkey,
+        salt,
+        options,
+    );
+    let full_bytes = send_proof_request(&body, prover_url).await?;
+    parse_proof_response(&full_bytes)
+}
+
+/// Parse the response of the prover into the zkLogin inputs and the metadata. The metadata fields
+/// are read from the top level of the response next to the proof.
+#[cfg(feature = "client")]
+pub(crate) fn parse_proof_response(bytes: &[u8]) -> Result<ProofResponse, FastCryptoError> {
+    Ok(ProofResponse {
+        inputs: serde_json::from_slice(bytes).map_err(|_| FastCryptoError::InvalidInput)?,
+        metadata: serde_json::from_slice(bytes).map_err(|_| FastCryptoError::InvalidInput)?,
+    })
+}
+
+/// Send a proof request to the prover and return the raw response.
+#[cfg(feature = "client")]
+async fn send_proof_request(body: &Value, prover_url: &str) -> Result<Vec<u8>, FastCryptoError> {
     let client = Client::new();
     let response = client
         .post(prover_url.to_string())
         .header("Content-Type", "application/json")
-        .json(&body)
+        .json(body)
         .send()
         .await
         .map_err(|_| FastCryptoError::InvalidInput)?;
@@ -189,26 +1603,60 @@ pub async fn get_proof(
     #[cfg(feature = "e2e")]
     println!("get_proof response: {:?}", full_bytes);
 
-    let get_proof_response: ZkLoginInputsReader =
-        serde_json::from_slice(&full_bytes).map_err(|_| FastCryptoError::InvalidInput)?;
-    Ok(get_proof_response)
+    Ok(full_bytes.to_vec())
 }
 
-/// Given a 33-byte public key bytes (flag || pk_bytes), returns the two Bn254Fr split at the 128 bit index.
+/// Build the JSON body of a proof request.
+#[cfg(feature = "client")]
+pub(crate) fn proof_request_body(
+    jwt_token: &str,
+    max_epoch: u64,
+    jwt_randomness: &str,
+    eph_pubkey: &str,
+    salt: &str,
+    options: &ProofRequestOptions,
+) -> Value {
+    let mut fields = vec![
+        ("jwt", json!(jwt_token)),
+        ("extendedEphemeralPublicKey", json!(eph_pubkey)),
+        ("maxEpoch", json!(max_epoch)),
+        ("jwtRandomness", json!(jwt_randomness)),
+        ("salt", json!(salt)),
+        ("keyClaimName", json!("sub")),
+    ];
+    if let Some(circuit_version) = options.circuit_version {
+        fields.push(("circuitVersion", json!(circuit_version)));
+    }
+    Value::Object(
+        fields
+            .into_iter()
+            .map(|(name, value)| (options.dialect.field_name(name), value))
+            .collect(),
+    )
+}
+
+/// Given public key bytes (flag || pk_bytes), returns the two Bn254Fr split at the 128 bit index.
+/// Returns [`FastCryptoError::InputLengthWrong`] if there are no bytes before the last 16 bytes
+/// or if the bytes before the last 16 bytes do not fit in a field element without reduction.
 pub fn split_to_two_frs(eph_pk_bytes: &[u8]) -> Result<(Bn254Fr, Bn254Fr), FastCryptoError> {
+    if !(MIN_EPH_PK_LENGTH..=MAX_EPH_PK_LENGTH).contains(&eph_pk_bytes.len()) {
+        return Err(FastCryptoError::InputLengthWrong {
+            expected: eph_pk_bytes
+                .len()
+                .clamp(MIN_EPH_PK_LENGTH, MAX_EPH_PK_LENGTH),
+            actual: eph_pk_bytes.len(),
+        });
+    }
     // Split the bytes deterministically such that the first element contains the first 128
     // bits of the hash, and the second element contains the latter ones.
     let (first_half, second_half) = eph_pk_bytes.split_at(eph_pk_bytes.len() - 16);
-    let first_bigint = BigUint::from_bytes_be(first_half);
-    // TODO: this is not safe if the buffer is large. Can we use a fixed size array for eph_pk_bytes?
-    let second_bigint = BigUint::from_bytes_be(second_half);
-
-    let eph_public_key_0 = Bn254Fr::from(first_bigint);
-    let eph_public_key_1 = Bn254Fr::from(second_bigint);
+    let eph_public_key_0 = biguint_to_bn254_fr(&BigUint::from_bytes_be(first_half))?;
+    let eph_public_key_1 = biguint_to_bn254_fr(&BigUint::from_bytes_be(second_half))?;
     Ok((eph_public_key_0, eph_public_key_1))
 }
 
 /// Call test issuer for a JWT token based on the request parameters.
+#[cfg(feature = "client")]
 pub async fn get_test_issuer_jwt_token(
     client: &reqwest::Client,
     nonce: &str,
@@ -238,6 +1686,7 @@ pub async fn get_test_issuer_jwt_token(
 }
 
 /// The response struct for the test issuer JWT token.
+#[cfg(feature = "client")]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TestIssuerJWTResponse {
     /// JWT token string.
@@ -87,7 +87,10 @@ impl PreparedVerifyingKey {
     /// alpha_g1_beta_g2, gamma_g2_neg_pc, delta_g2_neg_pc
     pub fn deserialize<V: Borrow<[u8]>>(bytes: &Vec<V>) -> Result<Self, FastCryptoError> {
         if bytes.len() != 4 {
-            return Err(FastCryptoError::InputLengthWrong(bytes.len()));
+            return Err(FastCryptoError::InputLengthWrong {
+                expected: 4,
+                actual: bytes.len(),
+            });
         }
 
         let vk_gamma_abc_g1_bytes = bytes[0].borrow();
@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use fastcrypto::{error::FastCryptoResult, jwt_utils::JWTHeader};
+#[cfg(feature = "client")]
 use reqwest::Client;
 use serde_json::Value;
 
@@ -9,14 +10,18 @@ use super::utils::split_to_two_frs;
 use crate::bn254::poseidon::poseidon_merkle_tree;
 use crate::bn254::FieldElement;
 use crate::zk_login_utils::{
-    g1_affine_from_str_projective, g2_affine_from_str_projective, Bn254FrElement, CircomG1,
-    CircomG2,
+    g1_affine_from_str_projective, g1_affine_to_str_projective, g2_affine_from_str_projective,
+    g2_affine_to_str_projective, Bn254FrElement, CircomG1, CircomG2,
 };
 pub use ark_bn254::{Bn254, Fr as Bn254Fr};
+use ark_bn254::{G1Affine, G2Affine};
+#[cfg(any(test, feature = "test-utils"))]
+use ark_ec::AffineRepr;
 pub use ark_ff::ToConstraintField;
 use ark_ff::Zero;
 use ark_groth16::Proof;
 pub use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use fastcrypto::encoding::{Encoding, Hex};
 use fastcrypto::error::FastCryptoError;
 use itertools::Itertools;
 use num_bigint::BigUint;
@@ -24,6 +29,7 @@ use regex::Regex;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering::{Equal, Greater, Less};
+#[cfg(feature = "client")]
 use std::error::Error;
 use std::str::FromStr;
 
@@ -85,8 +91,9 @@ impl ProviderConfig {
     }
 }
 
-/// Supported OIDC providers.
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+/// Supported OIDC providers. The derived ordering follows the declaration order of the variants
+/// (and the tenant strings for [`OIDCProvider::AwsTenant`]), so new variants should be appended.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub enum OIDCProvider {
     /// See https://accounts.google.com/.well-known/openid-configuration
     Google,
@@ -360,6 +367,7 @@ fn trim(str: String) -> String {
 }
 
 /// Fetch JWKs from the given provider and return a list of JwkId -> JWK.
+#[cfg(feature = "client")]
 pub async fn fetch_jwks(
     provider: &OIDCProvider,
     client: &Client,
@@ -417,6 +425,7 @@ pub fn parse_jwks(
 #[serde(rename_all = "camelCase")]
 pub struct Claim {
     value: String,
+    #[serde(alias = "index_mod_4")]
     index_mod_4: u8,
 }
 
@@ -457,8 +466,11 @@ pub struct ZkLoginInputs {
 #[derive(Debug, Clone, JsonSchema, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ZkLoginInputsReader {
+    #[serde(alias = "proof_points")]
     proof_points: ZkLoginProof,
+    #[serde(alias = "iss_base64_details")]
     iss_base64_details: Claim,
+    #[serde(alias = "header_base64")]
     header_base64: String,
     #[serde(skip)]
     jwt_details: JWTDetails,
@@ -488,6 +500,45 @@ impl ZkLoginInputs {
         .init()
     }
 
+    /// Construct the inputs from proof points and JWT details stored separately, e.g. in separate
+    /// database columns. The points a, b and c are hex encoded compressed arkworks points (G1, G2 and
+    /// G1 respectively), and the iss details are the Base64 encoded iss claim with its index mod 4
+    /// as returned by the prover. Each component is validated and an error names the first one
+    /// that is invalid.
+    pub fn from_proof_components(
+        a: &str,
+        b: &str,
+        c: &str,
+        iss_base64_value: &str,
+        iss_index_mod_4: u8,
+        header_base64: &str,
+        address_seed: &str,
+    ) -> Result<Self, FastCryptoError> {
+        let invalid = |component: &str| {
+            FastCryptoError::GeneralError(format!("Invalid proof component {}", component))
+        };
+        let proof = Proof::<Bn254> {
+            a: decode_point::<G1Affine>(a).map_err(|_| invalid("a"))?,
+            b: decode_point::<G2Affine>(b).map_err(|_| invalid("b"))?,
+            c: decode_point::<G1Affine>(c).map_err(|_| invalid("c"))?,
+        };
+        JWTHeader::new(header_base64).map_err(|_| invalid("header_base64"))?;
+        let address_seed =
+            Bn254FrElement::from_str(address_seed).map_err(|_| invalid("address_seed"))?;
+        ZkLoginInputs {
+            proof_points: ZkLoginProof::from_arkworks(&proof),
+            iss_base64_details: Claim {
+                value: iss_base64_value.to_string(),
+                index_mod_4: iss_index_mod_4,
+            },
+            header_base64: header_base64.to_string(),
+            address_seed,
+            jwt_details: JWTDetails::default(),
+        }
+        .init()
+        .map_err(|_| invalid("iss_base64_details"))
+    }
+
     /// Initialize JWTDetails by parsing header_base64 and iss_base64_details.
     pub fn init(&mut self) -> Result<Self, FastCryptoError> {
         self.jwt_details = JWTDetails::new(&self.header_base64, &self.iss_base64_details)?;
@@ -548,6 +599,66 @@ impl ZkLoginInputs {
         ])
     }
 }
+
+impl ZkLoginInputsReader {
+    /// Calculate the public inputs hash the Groth16 proof is verified against, i.e. the single
+    /// field element committing to the address seed, the ephemeral public key, max_epoch, the iss
+    /// and header details and the JWK modulus. See [`ZkLoginInputs::calculate_all_inputs_hash`].
+    /// The reader does not carry the address seed, the ephemeral public key, the modulus or
+    /// max_epoch, so the caller must supply them.
+    pub fn public_inputs_hash(
+        &self,
+        address_seed: &str,
+        eph_pk_bytes: &[u8],
+        modulus: &[u8],
+        max_epoch: u64,
+    ) -> Result<Bn254FrElement, FastCryptoError> {
+        let inputs = ZkLoginInputs::from_reader(self.clone(), address_seed)?;
+        let hash = inputs.calculate_all_inputs_hash(eph_pk_bytes, modulus, max_epoch)?;
+        Ok((&hash).into())
+    }
+
+    /// Return a structurally valid reader for testing code that handles zkLogin inputs without a
+    /// prover, e.g. serialization or address derivation. The proof points are the generators of G1
+    /// and G2 and the iss details and header are those of a Google JWT, so the reader parses and
+    /// converts to [`ZkLoginInputs`] with any address seed, but the proof must never verify.
+    #[cfg(any(test, feature = "test-utils"))]
+    pub fn dummy() -> Self {
+        let generator = Proof {
+            a: G1Affine::generator(),
+            b: G2Affine::generator(),
+            c: G1Affine::generator(),
+        };
+        Self {
+            proof_points: ZkLoginProof::from_arkworks(&generator),
+            iss_base64_details: Claim {
+                value: "yJpc3MiOiJodHRwczovL2FjY291bnRzLmdvb2dsZS5jb20iLC".to_string(),
+                index_mod_4: 1,
+            },
+            header_base64: "eyJhbGciOiJSUzI1NiIsImtpZCI6ImM5YWZkYTM2ODJlYmYwOWViMzA1NWMxYzRiZDM5Yjc1MWZiZjgxOTUiLCJ0eXAiOiJKV1QifQ".to_string(),
+            jwt_details: JWTDetails::default(),
+        }
+    }
+
+    /// Return the Base64 encoded header of the JWT the proof was generated for.
+    pub fn get_header_base64(&self) -> &str {
+        &self.header_base64
+    }
+
+    /// Return the (iss, kid) identifying the JWK the proof was generated against, parsed from the
+    /// iss details and the JWT header.
+    pub fn get_jwk_id(&self) -> Result<JwkId, FastCryptoError> {
+        let details = JWTDetails::new(&self.header_base64, &self.iss_base64_details)?;
+        Ok(JwkId::new(details.iss, details.kid))
+    }
+
+    /// Serialize back to the JSON format returned by the proving service. For a response in compact
+    /// form with canonical decimal field elements, this gives the exact bytes that were parsed.
+    pub fn to_prover_json(&self) -> Result<String, FastCryptoError> {
+        serde_json::to_string(self).map_err(|e| FastCryptoError::GeneralError(e.to_string()))
+    }
+}
+
 /// The struct for zk login proof.
 #[derive(Debug, Clone, JsonSchema, Serialize, Deserialize)]
 pub struct ZkLoginProof {
@@ -572,6 +683,28 @@ impl ZkLoginProof {
             c: g1_affine_from_str_projective(&self.c)?,
         })
     }
+
+    /// Convert an arkworks proof to the Circom format, i.e. the inverse of [`Self::as_arkworks`].
+    pub fn from_arkworks(proof: &Proof<Bn254>) -> Self {
+        ZkLoginProof {
+            a: g1_affine_to_str_projective(&proof.a),
+            b: g2_affine_to_str_projective(&proof.b),
+            c: g1_affine_to_str_projective(&proof.c),
+        }
+    }
+}
+
+/// Decode a hex encoded compressed arkworks point, checking that it is on the curve and in the
+/// prime order subgroup.
+fn decode_point<P: CanonicalDeserialize>(hex: &str) -> Result<P, FastCryptoError> {
+    let bytes = Hex::decode(hex).map_err(|_| FastCryptoError::InvalidInput)?;
+    let mut reader = bytes.as_slice();
+    let point =
+        P::deserialize_compressed(&mut reader).map_err(|_| FastCryptoError::InvalidInput)?;
+    if !reader.is_empty() {
+        return Err(FastCryptoError::InvalidInput);
+    }
+    Ok(point)
 }
 
 /// Parse the extended claim json value to its claim value, using the expected claim key.
@@ -682,8 +815,13 @@ fn bitarray_to_bytearray(bits: &[u8]) -> FastCryptoResult<Vec<u8>> {
         .collect())
 }
 
-/// Pads a stream of bytes and maps it to a field element
+/// Pads a stream of bytes and maps it to a field element. The circuit hashes the string byte by
+/// byte, so only ASCII strings are supported and [`FastCryptoError::InvalidInput`] is returned for
+/// any non-ASCII character.
 pub fn hash_ascii_str_to_field(str: &str, max_size: u8) -> Result<Bn254Fr, FastCryptoError> {
+    if !str.is_ascii() {
+        return Err(FastCryptoError::InvalidInput);
+    }
     let str_padded = str_to_padded_char_codes(str, max_size)?;
     hash_to_field(&str_padded, 8, PACK_WIDTH)
 }
@@ -767,7 +905,10 @@ fn big_int_array_to_bits(integers: &[BigUint], intended_size: usize) -> FastCryp
 /// This functions must be equivalent with the one found in the zk_login circuit.
 pub(crate) fn poseidon_zk_login(inputs: &[Bn254Fr]) -> FastCryptoResult<Bn254Fr> {
     if inputs.is_empty() || inputs.len() > 32 {
-        return Err(FastCryptoError::InputLengthWrong(inputs.len()));
+        return Err(FastCryptoError::InputLengthWrong {
+            expected: inputs.len().clamp(1, 32),
+            actual: inputs.len(),
+        });
     }
     poseidon_merkle_tree(&inputs.iter().map(|x| FieldElement(*x)).collect_vec()).map(|x| x.0)
 }
@@ -6,13 +6,21 @@ use std::str::FromStr;
 use ark_snark::SNARK;
 use fastcrypto::rsa::{Base64UrlUnpadded, Encoding};
 
-use super::zk_login::{JwkId, ZkLoginInputs, JWK};
-use crate::bn254::utils::{gen_address_seed_with_salt_hash, get_zk_login_address};
+#[cfg(feature = "client")]
+use super::zk_login::{fetch_jwks, OIDCProvider};
+use super::zk_login::{JwkId, ZkLoginInputs, ZkLoginInputsReader, JWK};
+use crate::bn254::utils::{
+    gen_address_seed, gen_address_seed_with_salt_hash, get_zk_login_address, CircuitParams,
+};
 use crate::zk_login_utils::{
     g1_affine_from_str_projective, g2_affine_from_str_projective, Bn254FqElement, Bn254FrElement,
 };
+use ark_bn254::G1Projective;
 pub use ark_bn254::{Bn254, Fr as Bn254Fr};
+use ark_ec::pairing::{Pairing, PairingOutput};
+use ark_ec::CurveGroup;
 pub use ark_ff::ToConstraintField;
+use ark_ff::{UniformRand, Zero};
 use ark_groth16::{Groth16, PreparedVerifyingKey, Proof, VerifyingKey};
 pub use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
 use fastcrypto::error::{FastCryptoError, FastCryptoResult};
@@ -327,6 +335,24 @@ pub fn verify_zk_login(
     all_jwk: &ImHashMap<JwkId, JWK>,
     env: &ZkLoginEnv,
 ) -> Result<(), FastCryptoError> {
+    let (proof, public_input) =
+        proof_and_public_input(input, max_epoch, eph_pubkey_bytes, all_jwk)?;
+    match verify_zk_login_proof_with_fixed_vk(env, &proof, &[public_input]) {
+        Ok(true) => Ok(()),
+        Ok(false) | Err(_) => Err(FastCryptoError::GeneralError(
+            "Groth16 proof verify failed".to_string(),
+        )),
+    }
+}
+
+/// Load the JWK of the input and return the proof and the public input it should be verified
+/// against.
+fn proof_and_public_input(
+    input: &ZkLoginInputs,
+    max_epoch: u64,
+    eph_pubkey_bytes: &[u8],
+    all_jwk: &ImHashMap<JwkId, JWK>,
+) -> Result<(Proof<Bn254>, Bn254Fr), FastCryptoError> {
     // Load the expected JWK based on (iss, kid).
     let (iss, kid) = (input.get_iss().to_string(), input.get_kid().to_string());
     let jwk = all_jwk
@@ -341,14 +367,184 @@ pub fn verify_zk_login(
     })?;
 
     // Calculat all inputs hash and passed to the verification function.
+    Ok((
+        input.get_proof().as_arkworks()?,
+        input.calculate_all_inputs_hash(eph_pubkey_bytes, &modulus, max_epoch)?,
+    ))
+}
+
+/// Verify a batch of zkLogin inputs, each given with its max_epoch and ephemeral public key, and
+/// return the result of [`verify_zk_login`] for each of them in the same order.
+///
+/// The proofs are first checked together with a random linear combination, which takes one pairing
+/// per proof plus two instead of three per proof. Since the coefficients are random, the combined
+/// check only passes if all proofs are valid, except with negligible probability. If it fails, each
+/// proof is verified separately so one invalid proof does not fail the others.
+pub fn batch_verify_zk_login(
+    batch: &[(&ZkLoginInputs, u64, &[u8])],
+    all_jwk: &ImHashMap<JwkId, JWK>,
+    env: &ZkLoginEnv,
+) -> Vec<Result<(), FastCryptoError>> {
+    let prepared: Vec<_> = batch
+        .iter()
+        .map(|(input, max_epoch, eph_pubkey_bytes)| {
+            proof_and_public_input(input, *max_epoch, eph_pubkey_bytes, all_jwk)
+        })
+        .collect();
+    let valid: Vec<_> = prepared.iter().filter_map(|p| p.as_ref().ok()).collect();
+    let all_valid = batch_verify_groth16(fixed_vk(env), &valid);
+
+    prepared
+        .into_iter()
+        .map(|p| {
+            let (proof, public_input) = p?;
+            match all_valid
+                || verify_zk_login_proof_with_fixed_vk(env, &proof, &[public_input])
+                    .unwrap_or(false)
+            {
+                true => Ok(()),
+                false => Err(FastCryptoError::GeneralError(
+                    "Groth16 proof verify failed".to_string(),
+                )),
+            }
+        })
+        .collect()
+}
+
+/// Check a random linear combination of the Groth16 verification equations of the given proofs:
+/// prod_i e(r_i A_i, B_i) * e(sum_i r_i L_i, -gamma) * e(sum_i r_i C_i, -delta) = e(alpha, beta)^(sum_i r_i)
+/// where L_i is the public input of proof i prepared with the verifying key.
+fn batch_verify_groth16(
+    pvk: &PreparedVerifyingKey<Bn254>,
+    proofs: &[&(Proof<Bn254>, Bn254Fr)],
+) -> bool {
+    let mut rng = rand::thread_rng();
+    let mut g1 = Vec::with_capacity(proofs.len() + 2);
+    let mut g2 = Vec::with_capacity(proofs.len() + 2);
+    let mut inputs_sum = G1Projective::zero();
+    let mut c_sum = G1Projective::zero();
+    let mut r_sum = Bn254Fr::zero();
+    for (proof, public_input) in proofs {
+        let r = Bn254Fr::rand(&mut rng);
+        let prepared_inputs = match Groth16::<Bn254>::prepare_inputs(pvk, &[*public_input]) {
+            Ok(prepared_inputs) => prepared_inputs,
+            Err(_) => return false,
+        };
+        inputs_sum += prepared_inputs * r;
+        c_sum += proof.c * r;
+        r_sum += r;
+        g1.push((proof.a * r).into_affine());
+        g2.push(<Bn254 as Pairing>::G2Prepared::from(proof.b));
+    }
+    g1.push(inputs_sum.into_affine());
+    g2.push(pvk.gamma_g2_neg_pc.clone());
+    g1.push(c_sum.into_affine());
+    g2.push(pvk.delta_g2_neg_pc.clone());
+    Bn254::multi_pairing(g1, g2) == PairingOutput(pvk.alpha_g1_beta_g2) * r_sum
+}
+
+/// Same as [`verify_zk_login`], but if the JWK for the (iss, kid) of the input is not in `all_jwk`,
+/// e.g. because the provider has rotated its keys, the JWKs of the issuer are fetched once, added to
+/// `all_jwk` and the verification is retried. A kid that is still unknown after the refresh gives a
+/// "JWK not found after refresh" error, while errors from verifying against the refreshed JWK, e.g.
+/// an invalid proof, are returned as given by [`verify_zk_login`].
+#[cfg(feature = "client")]
+pub async fn verify_zk_login_with_jwk_refresh(
+    input: &ZkLoginInputs,
+    max_epoch: u64,
+    eph_pubkey_bytes: &[u8],
+    all_jwk: &mut ImHashMap<JwkId, JWK>,
+    env: &ZkLoginEnv,
+    client: &reqwest::Client,
+) -> Result<(), FastCryptoError> {
+    let jwk_id = JwkId::new(input.get_iss().to_string(), input.get_kid().to_string());
+    if all_jwk.contains_key(&jwk_id) {
+        return verify_zk_login(input, max_epoch, eph_pubkey_bytes, all_jwk, env);
+    }
+
+    let provider = OIDCProvider::from_iss(input.get_iss())?;
+    all_jwk.extend(fetch_jwks(&provider, client).await?);
+    if !all_jwk.contains_key(&jwk_id) {
+        return Err(FastCryptoError::GeneralError(format!(
+            "JWK not found after refresh ({} - {})",
+            jwk_id.iss, jwk_id.kid
+        )));
+    }
+    verify_zk_login(input, max_epoch, eph_pubkey_bytes, all_jwk, env)
+}
+
+/// Check that the JWK a proof was generated against, identified by the iss and kid of the proof,
+/// is among the JWKs currently published by the provider, e.g. as returned by [`fetch_jwks`]. This
+/// rejects proofs bound to a key the provider has rotated out. Only the identifier is checked here,
+/// the modulus of the JWK is checked when the proof itself is verified, e.g. with
+/// [`verify_zk_login`].
+pub fn verify_proof_jwk_current(
+    reader: &ZkLoginInputsReader,
+    jwks: &[(JwkId, JWK)],
+) -> Result<(), FastCryptoError> {
+    let jwk_id = reader.get_jwk_id()?;
+    match jwks.iter().any(|(id, _)| *id == jwk_id) {
+        true => Ok(()),
+        false => Err(FastCryptoError::GeneralError(format!(
+            "JWK not currently published ({} - {})",
+            jwk_id.iss, jwk_id.kid
+        ))),
+    }
+}
+
+/// The parameters a user knows independently of the prover, from which the public input of a
+/// zkLogin proof can be recomputed. The jwt_randomness is not included since it is a private input
+/// to the circuit and only enters the public input through the nonce.
+#[derive(Debug, Clone)]
+pub struct PublicInputParams {
+    /// The iss claim of the JWT.
+    pub iss: String,
+    /// The aud claim of the JWT.
+    pub aud: String,
+    /// The sub claim of the JWT.
+    pub sub: String,
+    /// The user salt.
+    pub salt: String,
+    /// The extended ephemeral public key, i.e. the flag followed by the public key bytes.
+    pub eph_pk_bytes: Vec<u8>,
+    /// The max epoch committed to in the nonce.
+    pub max_epoch: u64,
+    /// The JWK the JWT was signed with.
+    pub jwk: JWK,
+    /// The environment of the verifying key to use.
+    pub env: ZkLoginEnv,
+}
+
+/// Check that the proof returned by a prover corresponds to the given parameters: the iss of the
+/// proof must match and the proof must verify against the public input recomputed from the
+/// parameters. This detects a malicious or buggy prover returning a proof for other inputs.
+pub fn verify_public_inputs(
+    reader: &ZkLoginInputsReader,
+    params: &PublicInputParams,
+) -> Result<(), FastCryptoError> {
+    let address_seed = gen_address_seed(&params.salt, "sub", &params.sub, &params.aud)?;
+    let inputs = ZkLoginInputs::from_reader(reader.clone(), &address_seed)?;
+    if inputs.get_iss() != params.iss {
+        return Err(FastCryptoError::GeneralError(format!(
+            "Proof is for iss {} but expected {}",
+            inputs.get_iss(),
+            params.iss
+        )));
+    }
+
+    let modulus = Base64UrlUnpadded::decode_vec(&params.jwk.n).map_err(|_| {
+        FastCryptoError::GeneralError("Invalid Base64 encoded jwk modulus".to_string())
+    })?;
+    let public_inputs =
+        inputs.calculate_all_inputs_hash(&params.eph_pk_bytes, &modulus, params.max_epoch)?;
     match verify_zk_login_proof_with_fixed_vk(
-        env,
-        &input.get_proof().as_arkworks()?,
-        &[input.calculate_all_inputs_hash(eph_pubkey_bytes, &modulus, max_epoch)?],
+        &params.env,
+        &inputs.get_proof().as_arkworks()?,
+        &[public_inputs],
     ) {
         Ok(true) => Ok(()),
         Ok(false) | Err(_) => Err(FastCryptoError::GeneralError(
-            "Groth16 proof verify failed".to_string(),
+            "Proof does not match the public inputs".to_string(),
         )),
     }
 }
@@ -359,12 +555,16 @@ pub fn verify_zk_login_proof_with_fixed_vk(
     proof: &Proof<Bn254>,
     public_inputs: &[Bn254Fr],
 ) -> Result<bool, FastCryptoError> {
-    let vk = match usage {
+    Groth16::<Bn254>::verify_with_processed_vk(fixed_vk(usage), public_inputs, proof)
+        .map_err(|e| FastCryptoError::GeneralError(e.to_string()))
+}
+
+/// The prepared verifying key of the given environment.
+fn fixed_vk(usage: &ZkLoginEnv) -> &'static PreparedVerifyingKey<Bn254> {
+    match usage {
         ZkLoginEnv::Prod => &GLOBAL_VERIFYING_KEY,
         ZkLoginEnv::Test => &INSECURE_VERIFYING_KEY,
-    };
-    Groth16::<Bn254>::verify_with_processed_vk(vk, public_inputs, proof)
-        .map_err(|e| FastCryptoError::GeneralError(e.to_string()))
+    }
 }
 
 /// Verify that the given parameters (name, value, aud, iss and salt_hash) were used to generate the
@@ -377,7 +577,8 @@ pub fn verify_zk_login_id(
     iss: &str,
     salt_hash: &str,
 ) -> FastCryptoResult<()> {
-    let address_seed = gen_address_seed_with_salt_hash(salt_hash, name, value, aud)?;
+    let address_seed =
+        gen_address_seed_with_salt_hash(salt_hash, name, value, aud, &CircuitParams::default())?;
     verify_zk_login_iss(address, &address_seed, iss)
 }
 
@@ -4,6 +4,8 @@
 use ark_bn254::{Fq, Fq2, Fr, G1Affine, G1Projective, G2Affine, G2Projective};
 use ark_ff::BigInteger;
 use ark_ff::PrimeField;
+use ark_ff::{One, Zero};
+use fastcrypto::encoding::{Encoding, Hex};
 use fastcrypto::error::FastCryptoError;
 use num_bigint::BigUint;
 use schemars::JsonSchema;
@@ -129,6 +131,62 @@ impl<'de> Deserialize<'de> for Bn254FrElement {
     }
 }
 
+/// Encode a Bn254FrElement as a "0x" prefixed hex string of its 32 big-endian bytes. This is the
+/// hex counterpart of the decimal string given by `to_string`.
+pub fn bn254_fr_to_hex(f: &Bn254FrElement) -> String {
+    Hex::encode_with_format(f.padded())
+}
+
+/// Decode a "0x" prefixed hex string of 32 big-endian bytes, as given by [`bn254_fr_to_hex`]. Returns
+/// an error if the prefix is missing, if the string does not encode exactly 32 bytes or if the
+/// value is not a canonical field element, i.e. not smaller than the field modulus.
+pub fn bn254_fr_from_hex(s: &str) -> Result<Bn254FrElement, FastCryptoError> {
+    let hex = s.strip_prefix("0x").ok_or(FastCryptoError::InvalidInput)?;
+    let bytes: [u8; 32] = Hex::decode(hex)?.try_into().map_err(|bytes: Vec<u8>| {
+        FastCryptoError::InputLengthWrong {
+            expected: 32,
+            actual: bytes.len(),
+        }
+    })?;
+    let element = Bn254FrElement(bytes);
+    if Bn254FrElement::from(&Fr::from(&element)) != element {
+        return Err(FastCryptoError::InvalidInput);
+    }
+    Ok(element)
+}
+
+/// Convert a big integer to a field element. Returns an error if the value is not smaller than the
+/// field modulus instead of reducing it.
+pub fn biguint_to_bn254_fr(value: &BigUint) -> Result<Fr, FastCryptoError> {
+    if *value >= BigUint::from(Fr::MODULUS) {
+        return Err(FastCryptoError::InvalidInput);
+    }
+    Ok(Fr::from(value.clone()))
+}
+
+/// Convert a field element to its canonical representative as a big integer, i.e. the inverse of
+/// [`biguint_to_bn254_fr`].
+pub fn bn254_fr_to_biguint(f: &Fr) -> BigUint {
+    BigUint::from(f.into_bigint())
+}
+
+/// Encode a field element as its canonical decimal string, i.e. without leading zeros. This is the
+/// format of public inputs and other field elements in the JSON of snarkjs and the prover.
+pub fn fr_to_decimal_string(f: &Fr) -> String {
+    bn254_fr_to_biguint(f).to_string()
+}
+
+/// Decode a canonical decimal string, as given by [`fr_to_decimal_string`]. Returns an error if the
+/// string contains anything but digits, has leading zeros or is not smaller than the field modulus.
+pub fn fr_from_decimal_string(s: &str) -> Result<Fr, FastCryptoError> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) || (s.len() > 1 && s.starts_with('0'))
+    {
+        return Err(FastCryptoError::InvalidInput);
+    }
+    let value = BigUint::parse_bytes(s.as_bytes(), 10).ok_or(FastCryptoError::InvalidInput)?;
+    biguint_to_bn254_fr(&value)
+}
+
 /// Convert Bn254FqElement type to arkworks' Fq.
 impl From<&Bn254FqElement> for Fq {
     fn from(f: &Bn254FqElement) -> Self {
@@ -143,6 +201,53 @@ impl From<&Bn254FrElement> for Fr {
     }
 }
 
+/// Convert arkworks' Fr to Bn254FrElement type.
+impl From<&Fr> for Bn254FrElement {
+    fn from(f: &Fr) -> Self {
+        Bn254FrElement(
+            f.into_bigint()
+                .to_bytes_be()
+                .try_into()
+                .expect("BN254 scalars are always 32 bytes"),
+        )
+    }
+}
+
+/// Convert arkworks' Fq to Bn254FqElement type.
+impl From<&Fq> for Bn254FqElement {
+    fn from(f: &Fq) -> Self {
+        Bn254FqElement(
+            f.into_bigint()
+                .to_bytes_be()
+                .try_into()
+                .expect("BN254 base field elements are always 32 bytes"),
+        )
+    }
+}
+
+/// Serialize an affine G1 point in arkworks format as projective coordinates, i.e. the inverse of
+/// [`g1_affine_from_str_projective`]. The point at infinity is serialized as (0, 1, 0).
+pub(crate) fn g1_affine_to_str_projective(g1: &G1Affine) -> CircomG1 {
+    let (x, y, z) = match g1.infinity {
+        true => (Fq::zero(), Fq::one(), Fq::zero()),
+        false => (g1.x, g1.y, Fq::one()),
+    };
+    vec![(&x).into(), (&y).into(), (&z).into()]
+}
+
+/// Serialize an affine G2 point in arkworks format as projective coordinates, i.e. the inverse of
+/// [`g2_affine_from_str_projective`]. The point at infinity is serialized as (0, 1, 0).
+pub(crate) fn g2_affine_to_str_projective(g2: &G2Affine) -> CircomG2 {
+    let (x, y, z) = match g2.infinity {
+        true => (Fq2::zero(), Fq2::one(), Fq2::zero()),
+        false => (g2.x, g2.y, Fq2::one()),
+    };
+    [x, y, z]
+        .iter()
+        .map(|c| vec![(&c.c0).into(), (&c.c1).into()])
+        .collect()
+}
+
 /// Deserialize a G1 projective point in BN254 serialized as a vector of three strings into an affine
 /// G1 point in arkworks format. Return an error if the input is not a vector of three strings or if
 /// any of the strings cannot be parsed as a field element.
@@ -189,7 +294,12 @@ mod test {
     use crate::zk_login_utils::Bn254FqElement;
     use std::str::FromStr;
 
-    use super::Bn254FrElement;
+    use super::{
+        biguint_to_bn254_fr, bn254_fr_from_hex, bn254_fr_to_biguint, bn254_fr_to_hex,
+        fr_from_decimal_string, fr_to_decimal_string, Bn254FrElement,
+    };
+    use ark_bn254::Fr;
+    use ark_ff::PrimeField;
     use num_bigint::BigUint;
     use proptest::prelude::*;
     #[test]
@@ -203,6 +313,96 @@ mod test {
         assert!(Bn254FrElement::from_str("garbage").is_err());
         assert!(Bn254FqElement::from_str("garbage").is_err());
     }
+    #[test]
+    fn fr_hex_round_trip() {
+        let decimal =
+            "16657007263003735230240998439420301694514420923267872433517882233836276100450";
+        let element = Bn254FrElement::from_str(decimal).unwrap();
+        let hex = bn254_fr_to_hex(&element);
+        assert_eq!(hex.len(), 66);
+        assert!(hex.starts_with("0x"));
+        assert_eq!(
+            BigUint::parse_bytes(&hex.as_bytes()[2..], 16)
+                .unwrap()
+                .to_string(),
+            decimal
+        );
+        let decoded = bn254_fr_from_hex(&hex).unwrap();
+        assert_eq!(decoded, element);
+        assert_eq!(decoded.to_string(), decimal);
+
+        // Zero is padded to 32 bytes.
+        let zero = Bn254FrElement::from_str("0").unwrap();
+        assert_eq!(bn254_fr_to_hex(&zero), format!("0x{}", "00".repeat(32)));
+
+        // The modulus is not canonical while the modulus minus one is.
+        let modulus = "0x30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000001";
+        assert!(bn254_fr_from_hex(modulus).is_err());
+        let max = "0x30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000000";
+        assert_eq!(
+            bn254_fr_from_hex(max).unwrap(),
+            Bn254FrElement::from(&-Fr::from(1u64))
+        );
+
+        // The prefix is required and the length must be 32 bytes.
+        assert!(bn254_fr_from_hex(&hex[2..]).is_err());
+        assert!(bn254_fr_from_hex("0x01").is_err());
+        assert!(bn254_fr_from_hex("0xzz").is_err());
+    }
+
+    #[test]
+    fn fr_decimal_round_trip() {
+        let decimal =
+            "16657007263003735230240998439420301694514420923267872433517882233836276100450";
+        let f = fr_from_decimal_string(decimal).unwrap();
+        assert_eq!(fr_to_decimal_string(&f), decimal);
+        assert_eq!(Bn254FrElement::from(&f).to_string(), decimal);
+
+        for f in [Fr::from(0u64), Fr::from(1u64), -Fr::from(1u64)] {
+            assert_eq!(fr_from_decimal_string(&fr_to_decimal_string(&f)), Ok(f));
+        }
+        assert_eq!(fr_to_decimal_string(&Fr::from(0u64)), "0");
+
+        // The modulus is not canonical while the modulus minus one is.
+        let modulus =
+            "21888242871839275222246405745257275088548364400416034343698204186575808495617";
+        assert!(fr_from_decimal_string(modulus).is_err());
+        let max = "21888242871839275222246405745257275088548364400416034343698204186575808495616";
+        assert_eq!(fr_from_decimal_string(max), Ok(-Fr::from(1u64)));
+
+        assert!(fr_from_decimal_string("").is_err());
+        assert!(fr_from_decimal_string("01").is_err());
+        assert!(fr_from_decimal_string("-1").is_err());
+        assert!(fr_from_decimal_string("+1").is_err());
+        assert!(fr_from_decimal_string("1 ").is_err());
+    }
+
+    #[test]
+    fn fr_biguint_round_trip() {
+        let modulus = BigUint::from(Fr::MODULUS);
+        let max = &modulus - 1u32;
+        assert_eq!(biguint_to_bn254_fr(&max), Ok(-Fr::from(1u64)));
+        assert_eq!(bn254_fr_to_biguint(&-Fr::from(1u64)), max);
+
+        // The modulus and larger values are rejected instead of reduced.
+        assert!(biguint_to_bn254_fr(&modulus).is_err());
+        assert!(biguint_to_bn254_fr(&(&modulus + 1u32)).is_err());
+        assert!(biguint_to_bn254_fr(&(BigUint::from(1u32) << 256)).is_err());
+
+        for value in [
+            BigUint::from(0u32),
+            BigUint::from(1u32),
+            BigUint::from(u128::MAX),
+        ] {
+            let f = biguint_to_bn254_fr(&value).unwrap();
+            assert_eq!(bn254_fr_to_biguint(&f), value);
+        }
+        assert_eq!(
+            biguint_to_bn254_fr(&BigUint::from(u128::MAX)),
+            Ok(Fr::from(u128::MAX))
+        );
+    }
+
     #[test]
     fn unpadded_slice() {
         let seed = Bn254FrElement([0; 32]);
@@ -104,7 +104,10 @@ where
             true => Ok(GenericByteArray {
                 bytes: GenericArray::clone_from_slice(bytes),
             }),
-            false => Err(FastCryptoError::InputLengthWrong(N::USIZE)),
+            false => Err(FastCryptoError::InputLengthWrong {
+                expected: N::USIZE,
+                actual: bytes.len(),
+            }),
         }
     }
 
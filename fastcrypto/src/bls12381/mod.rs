@@ -663,8 +663,17 @@ macro_rules! define_bls12381 {
                 pks: Vec<impl Iterator<Item = &'a Self::PubKey>>,
                 messages: &[&[u8]],
             ) -> Result<(), FastCryptoError> {
-                if signatures.len() != pks.len() || signatures.len() != messages.len() {
-                    return Err(FastCryptoError::InputLengthWrong(signatures.len()));
+                if pks.len() != signatures.len() {
+                    return Err(FastCryptoError::InputLengthWrong {
+                        expected: signatures.len(),
+                        actual: pks.len(),
+                    });
+                }
+                if messages.len() != signatures.len() {
+                    return Err(FastCryptoError::InputLengthWrong {
+                        expected: signatures.len(),
+                        actual: messages.len(),
+                    });
                 }
 
                 if signatures.is_empty() {
@@ -463,7 +463,10 @@ impl AggregateAuthenticator for Ed25519AggregateSignature {
         message: &[u8],
     ) -> Result<(), FastCryptoError> {
         if pks.len() != self.sigs.len() {
-            return Err(FastCryptoError::InputLengthWrong(self.sigs.len()));
+            return Err(FastCryptoError::InputLengthWrong {
+                expected: self.sigs.len(),
+                actual: pks.len(),
+            });
         }
         let mut batch = batch::Verifier::new();
 
@@ -482,8 +485,17 @@ impl AggregateAuthenticator for Ed25519AggregateSignature {
         pks: &[<Self::Sig as Authenticator>::PubKey],
         messages: &[&[u8]],
     ) -> Result<(), FastCryptoError> {
-        if pks.len() != self.sigs.len() || messages.len() != self.sigs.len() {
-            return Err(FastCryptoError::InputLengthWrong(self.sigs.len()));
+        if pks.len() != self.sigs.len() {
+            return Err(FastCryptoError::InputLengthWrong {
+                expected: self.sigs.len(),
+                actual: pks.len(),
+            });
+        }
+        if messages.len() != self.sigs.len() {
+            return Err(FastCryptoError::InputLengthWrong {
+                expected: self.sigs.len(),
+                actual: messages.len(),
+            });
         }
         let mut batch = batch::Verifier::new();
 
@@ -500,8 +512,17 @@ impl AggregateAuthenticator for Ed25519AggregateSignature {
         pks: Vec<impl ExactSizeIterator<Item = &'a Self::PubKey>>,
         messages: &[&[u8]],
     ) -> Result<(), FastCryptoError> {
-        if pks.len() != messages.len() || messages.len() != sigs.len() {
-            return Err(FastCryptoError::InputLengthWrong(sigs.len()));
+        if pks.len() != sigs.len() {
+            return Err(FastCryptoError::InputLengthWrong {
+                expected: sigs.len(),
+                actual: pks.len(),
+            });
+        }
+        if messages.len() != sigs.len() {
+            return Err(FastCryptoError::InputLengthWrong {
+                expected: sigs.len(),
+                actual: messages.len(),
+            });
         }
         let mut batch = batch::Verifier::new();
 
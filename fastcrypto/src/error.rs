@@ -28,9 +28,10 @@ pub enum FastCryptoError {
     #[error("Expected input of length at most {0}")]
     InputTooLong(usize),
 
-    /// Input length is wrong.
-    #[error("Expected input of length exactly {0}")]
-    InputLengthWrong(usize),
+    /// Input length is wrong. If a range of lengths is accepted, `expected` is the bound that was
+    /// violated.
+    #[error("Expected input of length {expected} but got {actual}")]
+    InputLengthWrong { expected: usize, actual: usize },
 
     /// Invalid signature was given to the function
     #[error("Invalid signature was given to the function")]
@@ -68,9 +68,21 @@ impl JWTHeader {
             .map_err(|_| FastCryptoError::InvalidInput)?;
         let header: JWTHeader =
             serde_json::from_slice(&header_bytes).map_err(|_| FastCryptoError::InvalidInput)?;
-        if header.alg != "RS256" {
+        if header.reject_insecure_alg().is_err() {
             return Err(FastCryptoError::GeneralError("Invalid header".to_string()));
         }
         Ok(header)
     }
+
+    /// Return [`FastCryptoError::InvalidInput`] unless the algorithm of the header is allowed for
+    /// zkLogin. Only RS256 is allowed since the circuit only verifies RSA signatures, which in
+    /// particular rejects unsigned tokens with `alg: none` and HMAC algorithms such as HS256, where a
+    /// public RSA key could be misused as the shared secret. Headers parsed with [`JWTHeader::new`]
+    /// are already checked, but headers deserialized otherwise are not.
+    pub fn reject_insecure_alg(&self) -> Result<(), FastCryptoError> {
+        match self.alg.as_str() {
+            "RS256" => Ok(()),
+            _ => Err(FastCryptoError::InvalidInput),
+        }
+    }
 }
@@ -27,7 +27,10 @@ impl<const RECOMMENDED_LENGTH: usize, const FIXED_LENGTH_ONLY: bool> ToFromBytes
 {
     fn from_bytes(bytes: &[u8]) -> Result<Self, FastCryptoError> {
         if FIXED_LENGTH_ONLY && bytes.len() != RECOMMENDED_LENGTH {
-            return Err(FastCryptoError::InputLengthWrong(RECOMMENDED_LENGTH));
+            return Err(FastCryptoError::InputLengthWrong {
+                expected: RECOMMENDED_LENGTH,
+                actual: bytes.len(),
+            });
         }
         Ok(Self {
             bytes: bytes.into(),
@@ -231,9 +231,10 @@ impl_base64_display_fmt!(Secp256k1Signature);
 impl ToFromBytes for Secp256k1Signature {
     fn from_bytes(bytes: &[u8]) -> Result<Self, FastCryptoError> {
         if bytes.len() != SECP256K1_SIGNATURE_LENGTH {
-            return Err(FastCryptoError::InputLengthWrong(
-                SECP256K1_SIGNATURE_LENGTH,
-            ));
+            return Err(FastCryptoError::InputLengthWrong {
+                expected: SECP256K1_SIGNATURE_LENGTH,
+                actual: bytes.len(),
+            });
         }
         NonrecoverableSignature::from_compact(bytes)
             .map(|sig| Secp256k1Signature {
@@ -56,9 +56,10 @@ serialize_deserialize_with_to_from_bytes!(
 impl ToFromBytes for Secp256k1RecoverableSignature {
     fn from_bytes(bytes: &[u8]) -> Result<Self, FastCryptoError> {
         if bytes.len() != SECP256K1_RECOVERABLE_SIGNATURE_SIZE {
-            return Err(FastCryptoError::InputLengthWrong(
-                SECP256K1_RECOVERABLE_SIGNATURE_SIZE,
-            ));
+            return Err(FastCryptoError::InputLengthWrong {
+                expected: SECP256K1_RECOVERABLE_SIGNATURE_SIZE,
+                actual: bytes.len(),
+            });
         }
         RecoveryId::from_i32(bytes[SECP256K1_RECOVERABLE_SIGNATURE_SIZE - 1] as i32)
             .and_then(|rec_id| {
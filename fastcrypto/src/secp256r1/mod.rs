@@ -272,7 +272,10 @@ impl ToFromBytes for Secp256r1PrivateKey {
                 privkey,
                 bytes: OnceCell::with_value(zeroize::Zeroizing::new(
                     <[u8; SECP256R1_PRIVATE_KEY_LENGTH]>::try_from(bytes).map_err(|_| {
-                        FastCryptoError::InputLengthWrong(SECP256R1_PRIVATE_KEY_LENGTH)
+                        FastCryptoError::InputLengthWrong {
+                            expected: SECP256R1_PRIVATE_KEY_LENGTH,
+                            actual: bytes.len(),
+                        }
                     })?,
                 )),
             }),
@@ -311,7 +314,10 @@ generate_bytes_representation!(
 impl ToFromBytes for Secp256r1Signature {
     fn from_bytes(bytes: &[u8]) -> Result<Self, FastCryptoError> {
         if bytes.len() != SECP256R1_SIGNATURE_LENTH {
-            return Err(FastCryptoError::InputLengthWrong(SECP256R1_SIGNATURE_LENTH));
+            return Err(FastCryptoError::InputLengthWrong {
+                expected: SECP256R1_SIGNATURE_LENTH,
+                actual: bytes.len(),
+            });
         }
 
         // This fails if either r or s are zero: https://docs.rs/ecdsa/0.16.6/src/ecdsa/lib.rs.html#209-219.
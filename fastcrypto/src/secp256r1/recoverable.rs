@@ -68,9 +68,10 @@ serialize_deserialize_with_to_from_bytes!(
 impl ToFromBytes for Secp256r1RecoverableSignature {
     fn from_bytes(bytes: &[u8]) -> Result<Self, FastCryptoError> {
         if bytes.len() != SECP256R1_RECOVERABLE_SIGNATURE_LENGTH {
-            return Err(FastCryptoError::InputLengthWrong(
-                SECP256R1_RECOVERABLE_SIGNATURE_LENGTH,
-            ));
+            return Err(FastCryptoError::InputLengthWrong {
+                expected: SECP256R1_RECOVERABLE_SIGNATURE_LENGTH,
+                actual: bytes.len(),
+            });
         }
 
         let recovery_id = bytes[SECP256R1_RECOVERABLE_SIGNATURE_LENGTH - 1];
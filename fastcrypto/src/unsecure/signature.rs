@@ -102,9 +102,13 @@ impl AsRef<[u8]> for UnsecurePublicKey {
 
 impl ToFromBytes for UnsecurePublicKey {
     fn from_bytes(bytes: &[u8]) -> Result<Self, FastCryptoError> {
-        let bytes_fixed: [u8; PUBLIC_KEY_LENGTH] = bytes
-            .try_into()
-            .map_err(|_| FastCryptoError::InputLengthWrong(PUBLIC_KEY_LENGTH))?;
+        let bytes_fixed: [u8; PUBLIC_KEY_LENGTH] =
+            bytes
+                .try_into()
+                .map_err(|_| FastCryptoError::InputLengthWrong {
+                    expected: PUBLIC_KEY_LENGTH,
+                    actual: bytes.len(),
+                })?;
         Ok(Self(bytes_fixed))
     }
 }
@@ -173,9 +177,13 @@ impl AsRef<[u8]> for UnsecureSignature {
 
 impl ToFromBytes for UnsecureSignature {
     fn from_bytes(bytes: &[u8]) -> Result<Self, FastCryptoError> {
-        let bytes_fixed: [u8; SIGNATURE_LENGTH] = bytes
-            .try_into()
-            .map_err(|_| FastCryptoError::InputLengthWrong(SIGNATURE_LENGTH))?;
+        let bytes_fixed: [u8; SIGNATURE_LENGTH] =
+            bytes
+                .try_into()
+                .map_err(|_| FastCryptoError::InputLengthWrong {
+                    expected: SIGNATURE_LENGTH,
+                    actual: bytes.len(),
+                })?;
         Ok(Self(bytes_fixed))
     }
 }
@@ -206,9 +214,13 @@ impl AsRef<[u8]> for UnsecurePrivateKey {
 
 impl ToFromBytes for UnsecurePrivateKey {
     fn from_bytes(bytes: &[u8]) -> Result<Self, FastCryptoError> {
-        let bytes: [u8; PRIVATE_KEY_LENGTH] = bytes
-            .try_into()
-            .map_err(|_| FastCryptoError::InputLengthWrong(PRIVATE_KEY_LENGTH))?;
+        let bytes: [u8; PRIVATE_KEY_LENGTH] =
+            bytes
+                .try_into()
+                .map_err(|_| FastCryptoError::InputLengthWrong {
+                    expected: PRIVATE_KEY_LENGTH,
+                    actual: bytes.len(),
+                })?;
         Ok(UnsecurePrivateKey(bytes))
     }
 }